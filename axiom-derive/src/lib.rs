@@ -0,0 +1,188 @@
+//! Procedural macros for Axiom. These are re-exported by the `axiom` crate and should be used
+//! from there.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::{quote, ToTokens};
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, Error, FnArg, Ident, ImplItem, ImplItemFn, ItemImpl, Type};
+
+/// Generates a processor method that dispatches messages to the handler methods of an `impl`
+/// block by the type of their content.
+///
+/// Every method in the block marked with `#[handler]` takes `&mut self`, the `ActorId` of the
+/// actor and a reference to the message content of some type, and returns a `Status`. The
+/// macro adds a method, named `handle` unless another name is given as in
+/// `#[processor(dispatch)]`, that can be passed to the spawn functions as the processor of the
+/// actor with the struct as its state. The method calls the handler for the type of the content
+/// of each message. A handler that takes a `&Message` gets the messages no other handler
+/// takes; without one those messages are ignored and [`Status::Processed`] is returned.
+///
+/// Two handlers can't take the same type. Note that types are compared as they are written, so
+/// a type written once with its path and once without it is not caught.
+///
+/// # Examples
+/// ```
+/// use axiom::*;
+///
+/// struct Data {
+///     value: i32,
+/// }
+///
+/// #[processor]
+/// impl Data {
+///     #[handler]
+///     fn handle_bool(&mut self, _aid: ActorId, message: &bool) -> Status {
+///         self.value += if *message { 1 } else { -1 };
+///         Status::Processed
+///     }
+///
+///     #[handler]
+///     fn handle_i32(&mut self, _aid: ActorId, message: &i32) -> Status {
+///         self.value += *message;
+///         Status::Processed
+///     }
+/// }
+///
+/// let system = ActorSystem::create(ActorSystemConfig::default());
+/// system.init_current();
+///
+/// let aid = system.spawn(Data { value: 0 }, Data::handle);
+/// aid.send(Message::new(11));
+/// aid.send(Message::new(true));
+/// ```
+///
+/// Handlers for the same type don't compile:
+/// ```compile_fail
+/// use axiom::*;
+///
+/// struct Data;
+///
+/// #[processor]
+/// impl Data {
+///     #[handler]
+///     fn first(&mut self, _aid: ActorId, _message: &i32) -> Status {
+///         Status::Processed
+///     }
+///
+///     #[handler]
+///     fn second(&mut self, _aid: ActorId, _message: &i32) -> Status {
+///         Status::Processed
+///     }
+/// }
+/// ```
+///
+/// [`Status::Processed`]: ../axiom/actors/enum.Status.html#variant.Processed
+#[proc_macro_attribute]
+pub fn processor(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let name = if attr.is_empty() {
+        Ident::new("handle", Span::call_site())
+    } else {
+        parse_macro_input!(attr as Ident)
+    };
+    let mut item = parse_macro_input!(item as ItemImpl);
+    match expand(name, &mut item) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+/// A handler method found in the `impl` block.
+struct Handler {
+    /// The name of the method.
+    method: Ident,
+    /// The type of the message content the method takes.
+    ty: Type,
+}
+
+/// Removes the `#[handler]` attributes from the methods of the `item` and returns it together
+/// with the generated processor method called `name`.
+fn expand(name: Ident, item: &mut ItemImpl) -> Result<proc_macro2::TokenStream, Error> {
+    let mut handlers: Vec<Handler> = Vec::new();
+    let mut fallback: Option<Ident> = None;
+    for impl_item in &mut item.items {
+        if let ImplItem::Fn(method) = impl_item {
+            let count = method.attrs.len();
+            method.attrs.retain(|attr| !attr.path().is_ident("handler"));
+            if method.attrs.len() == count {
+                continue;
+            }
+            let ty = message_type(method)?;
+            let key = ty.to_token_stream().to_string();
+            if key == "Message" || key.ends_with(":: Message") {
+                if let Some(other) = &fallback {
+                    let text = format!("`{}` already handles the other messages", other);
+                    return Err(Error::new(method.sig.ident.span(), text));
+                }
+                fallback = Some(method.sig.ident.clone());
+            } else if let Some(other) = handlers
+                .iter()
+                .find(|h| h.ty.to_token_stream().to_string() == key)
+            {
+                let text = format!(
+                    "`{}` already handles messages of type `{}`",
+                    other.method, key
+                );
+                return Err(Error::new(ty.span(), text));
+            } else {
+                handlers.push(Handler {
+                    method: method.sig.ident.clone(),
+                    ty,
+                });
+            }
+        }
+    }
+
+    let branches = handlers.iter().map(|Handler { method, ty }| {
+        quote! {
+            if let Some(content) = message.content_as::<#ty>() {
+                return self.#method(aid, &*content);
+            }
+        }
+    });
+    let otherwise = match fallback {
+        Some(method) => quote! { self.#method(aid, message) },
+        None => quote! { ::axiom::Status::Processed },
+    };
+    let (impl_generics, _, where_clause) = item.generics.split_for_impl();
+    let self_ty = &item.self_ty;
+    Ok(quote! {
+        #item
+
+        impl #impl_generics #self_ty #where_clause {
+            /// Processes a message by calling the handler for the type of its content.
+            pub fn #name(
+                &mut self,
+                aid: ::axiom::ActorId,
+                message: &::axiom::Message,
+            ) -> ::axiom::Status {
+                #(#branches)*
+                #otherwise
+            }
+        }
+    })
+}
+
+/// Returns the type of the message content that a handler `method` takes, checking that the
+/// method takes `self`, an actor id and a reference to the content.
+fn message_type(method: &ImplItemFn) -> Result<Type, Error> {
+    let inputs = &method.sig.inputs;
+    let malformed = || {
+        Error::new(
+            inputs.span(),
+            "a handler takes `&mut self`, an `ActorId` and a reference to the message content",
+        )
+    };
+    if inputs.len() != 3 || method.sig.receiver().is_none() {
+        return Err(malformed());
+    }
+    match &inputs[2] {
+        FnArg::Typed(arg) => match &*arg.ty {
+            Type::Reference(reference) => Ok((*reference.elem).clone()),
+            _ => Err(malformed()),
+        },
+        _ => Err(malformed()),
+    }
+}
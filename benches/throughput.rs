@@ -0,0 +1,84 @@
+//! Measures how many messages per second the actor system processes with each scheduler and
+//! different limits on the batches of messages that actors process per turn.
+//!
+//! Run with `cargo bench --bench throughput`.
+
+use axiom::*;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The number of actors that messages are sent to.
+const ACTORS: usize = 8;
+/// The number of messages sent to each actor.
+const MESSAGES: usize = 200_000;
+
+/// Sends the messages to the actors on a system using the `scheduler` and limiting batches to
+/// `max_batch_messages` and returns how long it took until all of the messages were processed.
+fn run(scheduler: SchedulerKind, max_batch_messages: u16) -> Duration {
+    let system = ActorSystem::create(ActorSystemConfig {
+        work_channel_size: 1000,
+        max_batch_messages,
+        scheduler,
+        ..ActorSystemConfig::default()
+    });
+    system.init_current();
+
+    let aids: Vec<ActorId> = (0..ACTORS)
+        .map(|_| {
+            system
+                .actor_builder()
+                .channel_size(1000)
+                .spawn(0_usize, |count: &mut usize, _: ActorId, _: &Message| {
+                    *count += 1;
+                    Status::Processed
+                })
+                .unwrap()
+        })
+        .collect();
+
+    // Each actor gets its own sender so that sending isn't the bottleneck.
+    let start = Instant::now();
+    let senders: Vec<_> = aids
+        .iter()
+        .cloned()
+        .map(|aid| {
+            let system = system.clone();
+            thread::spawn(move || {
+                system.init_current();
+                for _ in 0..MESSAGES {
+                    aid.send(Message::new(11));
+                }
+            })
+        })
+        .collect();
+    for sender in senders {
+        sender.join().unwrap();
+    }
+    // Every actor also processed its start message.
+    while aids.iter().any(|aid| aid.received() < MESSAGES + 1) {
+        thread::yield_now();
+    }
+    let elapsed = start.elapsed();
+
+    system.trigger_and_await_shutdown();
+    elapsed
+}
+
+fn main() {
+    for &scheduler in &[SchedulerKind::GlobalQueue, SchedulerKind::WorkStealing] {
+        for &max_batch_messages in &[1, 10, 100, 1000] {
+            // Each run gets its own thread as a thread can only have one current actor system.
+            let elapsed = thread::spawn(move || run(scheduler, max_batch_messages))
+                .join()
+                .unwrap();
+            let total = (ACTORS * MESSAGES) as f64;
+            println!(
+                "{:<12}  max_batch_messages: {:>4}  elapsed: {:>8.1?}  messages/sec: {:>12.0}",
+                format!("{:?}", scheduler),
+                max_batch_messages,
+                elapsed,
+                total / elapsed.as_secs_f64()
+            );
+        }
+    }
+}
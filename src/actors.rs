@@ -12,27 +12,69 @@
 //!
 //! The user should refer to test cases and examples as "how-to" guides for using Axiom.
 
+use crate::cluster::*;
+use crate::codec::*;
+use crate::dispatcher::*;
 use crate::message::*;
-use log::{error, warn};
+use crate::timers::*;
+use log::{debug, error, info, warn};
 use once_cell::sync::OnceCell;
 use secc::*;
 use serde::de::Deserializer;
 use serde::ser::Serializer;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::any::Any;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::future::Future;
 use std::hash::{Hash, Hasher};
+use std::io;
 use std::marker::{Send, Sync};
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::mpsc;
 use std::sync::{Arc, Condvar, Mutex, RwLock};
+use std::task::{Context, Poll, Waker};
 use std::thread;
 use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 use uuid::Uuid;
 
 // This holds the actor system in a threadlocal so that the user can obtain a clone of it
 // if needed at any time.
 std::thread_local! {
-    static ACTOR_SYSTEM: OnceCell<ActorSystem> = OnceCell::new();
+    static ACTOR_SYSTEM: OnceCell<ActorSystem> = const { OnceCell::new() };
+
+    /// The state of the pinned actor that the current thread runs, which never leaves the
+    /// thread. See [`ActorBuilder::spawn_pinned`].
+    static PINNED_STATE: RefCell<Option<Box<dyn Any>>> = const { RefCell::new(None) };
+}
+
+/// Counts a thread as running in the actor system for as long as the guard is held, which
+/// includes the unwinding of a panic on the thread.
+struct RunningThread(ActorSystem);
+
+impl RunningThread {
+    /// Counts a new running thread in the `system`.
+    fn start(system: ActorSystem) -> RunningThread {
+        *system.data.running_thread_count.0.lock().unwrap() += 1;
+        RunningThread(system)
+    }
+}
+
+impl Drop for RunningThread {
+    fn drop(&mut self) {
+        let (mutex, condvar) = &*self.0.data.running_thread_count;
+        let mut count = mutex.lock().unwrap();
+        *count -= 1;
+        // If this is the last thread exiting we will notify any waiters.
+        if *count == 0 {
+            condvar.notify_all();
+        }
+    }
 }
 
 /// Status of the message and potentially the actor as a resulting from processing a message
@@ -82,8 +124,84 @@ pub enum SystemMsg {
     Stop,
 
     /// A message sent to an actor when a monitored actor is stopped and thus not able to
-    /// process additional messages. The value is the `aid` of the actor that stopped.
-    Stopped(ActorId),
+    /// process additional messages.
+    Stopped {
+        /// The `aid` of the actor that stopped.
+        aid: ActorId,
+        /// The reason the actor stopped.
+        reason: StopReason,
+    },
+
+    /// A message sent to an actor that traps exits when an actor linked to it stops. Actors
+    /// that don't trap exits are stopped instead if the linked actor stopped abnormally. See
+    /// [`ActorSystem::link`] and [`ActorSystem::trap_exit`].
+    Exit {
+        /// The `aid` of the linked actor that stopped.
+        aid: ActorId,
+        /// The reason the linked actor stopped.
+        reason: StopReason,
+    },
+
+    /// A message sent to actors monitoring the cluster when a connection to another actor
+    /// system is established. The value is the UUID of the actor system that joined. See
+    /// [`ActorSystem::monitor_nodes`].
+    NodeJoined(Uuid),
+
+    /// A message sent to actors monitoring the cluster when the connection to another actor
+    /// system is lost, either because it was closed or because the actor system stopped
+    /// sending heartbeats. The value is the UUID of the actor system that left.
+    NodeLeft(Uuid),
+
+    /// A message sent to an actor registered under a global name when the same name turned
+    /// out to be registered to another actor elsewhere in the cluster, which typically happens
+    /// when a network partition heals. The other actor keeps the name and the value is the name
+    /// that was lost. See [`ActorSystem::register_global`].
+    GlobalNameLost(String),
+
+    /// A message sent to an actor with a receive timeout when it hasn't processed a message
+    /// for the duration of the timeout. The timeout starts again after the actor handles this
+    /// message, so an actor that keeps getting no messages gets this message repeatedly. See
+    /// [`ActorSystem::set_receive_timeout`].
+    ReceiveTimeout,
+}
+
+/// The reason an actor stopped which is sent to the monitors of the actor in a
+/// [`SystemMsg::Stopped`].
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum StopReason {
+    /// The actor stopped itself by returning [`Status::Stop`] or was sent a [`SystemMsg::Stop`].
+    Normal,
+
+    /// The actor was stopped from the outside with [`ActorSystem::stop`].
+    Forced,
+
+    /// The processor of the actor panicked. The value is the message of the panic.
+    Panic(String),
+
+    /// The channel of the actor failed while processing a message. The value describes the
+    /// error from the channel.
+    ChannelError(String),
+
+    /// The actor lives on a remote actor system that left the cluster or is not connected, so
+    /// the actor can no longer be reached.
+    NodeDown,
+
+    /// The actor system the actor lives on was shut down.
+    SystemShutdown,
+
+    /// The actor had already stopped when it was monitored so why it stopped is not known.
+    Unknown,
+
+    /// The actor was stopped because the linked actor with the given `aid` stopped abnormally.
+    LinkedExit(ActorId),
+}
+
+impl StopReason {
+    /// Checks whether the actor stopped because something went wrong. Only [`StopReason::Normal`]
+    /// and [`StopReason::SystemShutdown`] are not considered abnormal.
+    pub fn is_abnormal(&self) -> bool {
+        !matches!(self, StopReason::Normal | StopReason::SystemShutdown)
+    }
 }
 
 /// Errors returned from actors and other parts of the actor system.
@@ -99,9 +217,42 @@ pub enum ActorError {
     /// to be registered.
     NameAlreadyUsed(String),
 
-    /// Error Used for when an attempt is made to send a message to a remote actor. **This
-    /// error will be removed when remote actors are implemented.**
-    RemoteNotImplemented,
+    /// Error returned when an attempt is made to send a message to a remote actor on an actor
+    /// system that this actor system is not connected to. The error contains the UUID of the
+    /// remote actor system. See [`ActorSystem::connect`] for how to connect actor systems.
+    NotConnected(Uuid),
+
+    /// Error returned when no reply to a request sent with [`ActorId::ask`] arrived within
+    /// the timeout.
+    Timeout,
+
+    /// Error returned when replying to a message that was not sent with [`ActorId::ask`].
+    ReplyNotExpected,
+
+    /// Error returned when sending to an actor whose channel is full and whose overflow policy
+    /// is [`OverflowPolicy::Fail`].
+    ChannelFull,
+
+    /// Error returned when spawning an actor on a dispatcher that the actor system doesn't
+    /// have. The error contains the name of the dispatcher. See [`DispatcherConfig`].
+    UnknownDispatcher(String),
+
+    /// Error returned when the config of an actor system has more than one dispatcher with the
+    /// same name, counting the default dispatcher. The error contains the name. See
+    /// [`ActorSystemConfig::validate`].
+    DuplicateDispatcher(String),
+
+    /// Error returned when the function creating the state of a pinned actor panicked. The
+    /// error contains the panic message. See [`ActorBuilder::spawn_pinned`].
+    InitPanicked(String),
+
+    /// Error returned when a value can't be encoded or decoded with a [`Codec`]. The error
+    /// contains the description of the failure given by the codec.
+    Serialization(String),
+
+    /// Error returned when registering a name for a message type that another type already
+    /// has. The error contains the name. See [`Message::register_type`].
+    TypeNameAlreadyUsed(String),
 }
 
 /// An enum that holds a sender for an actor.
@@ -116,17 +267,146 @@ enum ActorSender {
         /// Holds a boolean to indicate if the actor is stopped. A stopped actor will no longer
         /// accept further messages to be sent.
         stopped: AtomicBool,
-        // The send side of the actor's message channel.
-        sender: SeccSender<Message>,
+        /// The message channels of the actor, which are shared with the actor. The receive
+        /// sides are needed to drop the oldest message when the overflow policy is
+        /// [`OverflowPolicy::DropOldest`].
+        mailbox: Arc<Mailbox>,
+        /// What to do with a user message sent when its channel is full.
+        overflow: OverflowPolicy,
+        /// Whether the actor is idle, waiting for a dispatcher thread or running.
+        schedule_state: ScheduleState,
     },
 
-    /// A sender that is used when an actor is on another actor system. The system will use
-    /// networking and serialization to send messages to the actor.
-    ///
-    /// FIXME (Issue #9) Implement remote actors.
+    /// A sender that is used when an actor is on another actor system. The message will be
+    /// serialized and sent over the connection to the actor system identified by the
+    /// `system_uuid` of the [`ActorId`] where it will be relayed to the actor's local channel.
     Remote,
 }
 
+/// One of the message channels of a local actor.
+struct Lane {
+    /// The send side of the channel.
+    sender: SeccSender<Message>,
+    /// The receive side of the channel.
+    receiver: Mutex<SeccReceiver<Message>>,
+}
+
+/// The message channels of a local actor. System messages get a lane of their own so that, for
+/// example, a [`SystemMsg::Stop`] isn't stuck behind a backlog of user messages, and user
+/// messages get a lane for each of the priority levels of the actor. See
+/// [`ActorConfig::priority_levels`]. The actor always receives from the most urgent lane that
+/// has receivable messages.
+struct Mailbox {
+    /// The lanes from the most urgent to the least: the system lane followed by the lanes of the
+    /// user priority levels from the highest to the lowest.
+    lanes: Vec<Lane>,
+}
+
+impl Mailbox {
+    /// Creates the mailbox of an actor with the given `config`. Every lane holds up to
+    /// [`ActorConfig::channel_size`] messages.
+    fn new(config: &ActorConfig) -> Mailbox {
+        let lanes = (0..=config.priority_levels.max(1))
+            .map(|_| {
+                let (sender, receiver) = secc::create(config.channel_size, config.poll_time);
+                Lane {
+                    sender,
+                    receiver: Mutex::new(receiver),
+                }
+            })
+            .collect();
+        Mailbox { lanes }
+    }
+
+    /// Returns the index of the lane for a `message` sent with the given `priority`. System
+    /// messages always go to the system lane and priorities above the highest level of the
+    /// actor are treated as the highest level.
+    fn lane_index(&self, message: &Message, priority: u8) -> usize {
+        if message.is_type::<SystemMsg>() {
+            0
+        } else {
+            let highest = self.lanes.len() - 2;
+            1 + highest - (priority as usize).min(highest)
+        }
+    }
+
+    /// Returns the index of the most urgent lane with a receivable message and a clone of that
+    /// message, if there is one.
+    fn peek(&self) -> Option<(usize, Message)> {
+        self.lanes.iter().enumerate().find_map(|(index, lane)| {
+            let receiver = lane.receiver.lock().unwrap();
+            receiver.peek().ok().map(|message| (index, message.clone()))
+        })
+    }
+
+    /// Clears the skips in every lane so that skipped messages are receivable again.
+    fn reset_skip(&self) -> Result<(), SeccErrors<Message>> {
+        for lane in &self.lanes {
+            lane.receiver.lock().unwrap().reset_skip()?;
+        }
+        Ok(())
+    }
+
+    /// Adds up a metric of the channels over all of the lanes.
+    fn total<F: Fn(&SeccSender<Message>) -> usize>(&self, metric: F) -> usize {
+        self.lanes.iter().map(|lane| metric(&lane.sender)).sum()
+    }
+}
+
+/// Tracks whether an actor is scheduled so that it is in the work queue at most once and is
+/// never left idle with receivable messages.
+///
+/// An idle actor is scheduled by whoever moves it from idle to scheduled, which is either a
+/// sender that just put a message in the channel or the dispatcher thread that just finished
+/// running it. The dispatcher marks the actor idle *before* it checks for receivable messages
+/// and a sender puts its message in the channel *before* it checks the state, so at least one
+/// of them sees the other and only the one that wins the change to scheduled enqueues it.
+struct ScheduleState(AtomicU8);
+
+impl ScheduleState {
+    /// The actor has no messages or has messages that arrived while it was finishing a run
+    /// and is about to be scheduled by whoever notices them first.
+    const IDLE: u8 = 0;
+    /// The actor is in the work queue waiting for a dispatcher thread.
+    const SCHEDULED: u8 = 1;
+    /// A dispatcher thread is processing the actor's messages.
+    const RUNNING: u8 = 2;
+
+    /// Creates the state of a new actor which is idle.
+    fn new() -> ScheduleState {
+        ScheduleState(AtomicU8::new(ScheduleState::IDLE))
+    }
+
+    /// Moves an idle actor to scheduled and returns whether the caller did so, in which case
+    /// the caller has to put the actor in the work queue.
+    fn try_schedule(&self) -> bool {
+        self.0
+            .compare_exchange(
+                ScheduleState::IDLE,
+                ScheduleState::SCHEDULED,
+                Ordering::SeqCst,
+                Ordering::SeqCst,
+            )
+            .is_ok()
+    }
+
+    /// Checks whether the actor is waiting for a thread to process it.
+    fn is_scheduled(&self) -> bool {
+        self.0.load(Ordering::SeqCst) == ScheduleState::SCHEDULED
+    }
+
+    /// Marks a scheduled actor as running once a dispatcher thread took it from the queue.
+    fn start_running(&self) {
+        let previous = self.0.swap(ScheduleState::RUNNING, Ordering::SeqCst);
+        debug_assert_eq!(ScheduleState::SCHEDULED, previous);
+    }
+
+    /// Marks a running actor as idle once the dispatcher thread is done with it.
+    fn stop_running(&self) {
+        self.0.store(ScheduleState::IDLE, Ordering::SeqCst);
+    }
+}
+
 impl fmt::Debug for ActorSender {
     fn fmt(&self, formatter: &'_ mut fmt::Formatter) -> fmt::Result {
         write!(
@@ -275,14 +555,114 @@ impl ActorId {
     /// }
     /// ```
     pub fn try_send(&self, message: Message) -> Result<(), ActorError> {
+        self.try_send_with_priority(message, 0)
+    }
+
+    /// Sends a message to the actor with the given `priority` as with [`ActorId::send`]. See
+    /// [`ActorId::try_send_with_priority`] for how the priority is used.
+    pub fn send_with_priority(&self, message: Message, priority: u8) {
+        match self.try_send_with_priority(message, priority) {
+            Ok(_) => (),
+            Err(e) => panic!("Error occurred sending to aid: {:?}", e),
+        }
+    }
+
+    /// Attempts to send a message to the actor with the given `priority` as with
+    /// [`ActorId::try_send`]. The actor receives the messages of higher priorities first and
+    /// messages of the same priority in the order they were sent. Priority 0 is the lowest and
+    /// the one used by the other send functions; priorities above the highest level the actor
+    /// was spawned with, see [`ActorConfig::priority_levels`], are treated as the highest
+    /// level. System messages are always received before user messages whatever the priority.
+    ///
+    /// # Examples
+    /// ```
+    /// use axiom::*;
+    ///
+    /// let system = ActorSystem::create(ActorSystemConfig::default());
+    /// system.init_current();
+    ///
+    /// let aid = system
+    ///     .actor_builder()
+    ///     .priority_levels(2)
+    ///     .spawn(0, |_: &mut usize, _: ActorId, _: &Message| Status::Processed)
+    ///     .unwrap();
+    ///
+    /// aid.try_send_with_priority(Message::new("urgent".to_string()), 1).unwrap();
+    /// ```
+    pub fn try_send_with_priority(&self, message: Message, priority: u8) -> Result<(), ActorError> {
+        self.send_local_or_remote(message, priority, true)
+    }
+
+    /// Attempts to send a message to the actor as with [`ActorId::try_send`] but fails with an
+    /// [`ActorError::ChannelFull`] where the send would wait for room in the channel. This is
+    /// for the threads of the actor system that must not be held up by a single actor.
+    pub(crate) fn try_send_without_blocking(&self, message: Message) -> Result<(), ActorError> {
+        self.send_local_or_remote(message, 0, false)
+    }
+
+    /// Sends a message to the actor with the given `priority`, waiting for room in a full
+    /// channel if the overflow policy asks for it and `block` is `true`.
+    fn send_local_or_remote(
+        &self,
+        message: Message,
+        priority: u8,
+        block: bool,
+    ) -> Result<(), ActorError> {
         match &self.data.sender {
-            ActorSender::Local { stopped, sender } => {
+            ActorSender::Local {
+                stopped,
+                mailbox,
+                overflow,
+                schedule_state,
+            } => {
                 if stopped.load(Ordering::Relaxed) {
                     Err(ActorError::ActorStopped)
                 } else {
-                    sender.send_await(message).unwrap();
-                    // FIXME Investigate if this could race the dispatcher threads.
-                    if sender.receivable() == 1 {
+                    let index = mailbox.lane_index(&message, priority);
+                    let Lane { sender, receiver } = &mailbox.lanes[index];
+                    // System messages are never dropped or refused.
+                    let overflow = if index == 0 {
+                        OverflowPolicy::Block
+                    } else {
+                        *overflow
+                    };
+                    let overflow = match overflow {
+                        OverflowPolicy::Block if !block => OverflowPolicy::Fail,
+                        overflow => overflow,
+                    };
+                    match overflow {
+                        OverflowPolicy::Block => sender.send_await(message).unwrap(),
+                        OverflowPolicy::Fail => {
+                            if sender.send(message).is_err() {
+                                return Err(ActorError::ChannelFull);
+                            }
+                        }
+                        OverflowPolicy::DropNewest => {
+                            if sender.send(message).is_err() {
+                                debug!("Channel of {:?} is full, dropping newest message", self);
+                                return Ok(());
+                            }
+                        }
+                        OverflowPolicy::DropOldest => {
+                            let mut message = message;
+                            while let Err(SeccErrors::Full(m)) = sender.send(message) {
+                                debug!("Channel of {:?} is full, dropping oldest message", self);
+                                message = m;
+                                // If every message in the channel was skipped there is nothing
+                                // that can be dropped so we wait for room instead.
+                                if receiver.lock().unwrap().pop().is_err() {
+                                    if !block {
+                                        return Err(ActorError::ChannelFull);
+                                    }
+                                    sender.send_await(message).unwrap();
+                                    break;
+                                }
+                            }
+                        }
+                    }
+                    // The message is in the channel before the state is checked. See
+                    // `ScheduleState` for why this matters.
+                    if schedule_state.try_schedule() {
                         // Schedule on the actor system set as current for this thread.
                         ActorSystem::current().schedule(self.clone())
                     };
@@ -290,8 +670,47 @@ impl ActorId {
                     Ok(())
                 }
             }
-            _ => Err(ActorError::RemoteNotImplemented),
+            ActorSender::Remote => ActorSystem::current().send_to_remote(self, message, priority),
+        }
+    }
+
+    /// Sends the `message` to the actor as a request and returns a handle that resolves to the
+    /// reply, or to an [`ActorError::Timeout`] if no reply arrives within the `timeout`. The
+    /// actor replies with [`Message::reply`]. The reply is correlated with the request by the
+    /// actor system of the calling thread so no actor is needed to receive it, which makes
+    /// this usable from within an actor as well as from any thread that called
+    /// [`ActorSystem::init_current`].
+    ///
+    /// # Examples
+    /// ```
+    /// use axiom::*;
+    /// use std::time::Duration;
+    ///
+    /// let system = ActorSystem::create(ActorSystemConfig::default());
+    /// system.init_current();
+    ///
+    /// let aid = system.spawn(0, |_: &mut usize, _: ActorId, message: &Message| {
+    ///     if message.is_request() {
+    ///         message.reply(Message::new("pong".to_string())).unwrap();
+    ///     }
+    ///     Status::Processed
+    /// });
+    ///
+    /// let handle = aid.ask(Message::new("ping".to_string()), Duration::from_secs(1));
+    /// let reply = handle.wait().unwrap();
+    /// assert_eq!("pong", *reply.content_as::<String>().unwrap());
+    /// ```
+    pub fn ask(&self, message: Message, timeout: Duration) -> AskHandle {
+        let system = ActorSystem::current();
+        let handle = system.register_ask(timeout);
+        let reply_to = ReplyTo {
+            system_uuid: system.uuid(),
+            request_id: handle.request_id,
+        };
+        if let Err(e) = self.try_send(message.with_reply_to(reply_to)) {
+            system.complete_ask(&handle.request_id, Err(e));
         }
+        handle
     }
 
     /// The unique UUID for this actor within the entire cluster.
@@ -309,6 +728,7 @@ impl ActorId {
     /// The name of the actor as assigned by the user at spawn time if any. Note that this name
     /// is guaranteed to be unique only within the actor system in which the actor was started,
     /// no guarantees are made that the name will be unique within a cluster of actor systems.
+    /// See [`ActorSystem::register_global`] for names that are unique within the cluster.
     #[inline]
     pub fn name(&self) -> Option<String> {
         self.data.name.clone()
@@ -317,11 +737,7 @@ impl ActorId {
     /// Determines if this actor lives on actor system in the calling thread.
     #[inline]
     pub fn is_local(&self) -> bool {
-        if let ActorSender::Local { .. } = self.data.sender {
-            true
-        } else {
-            false
-        }
+        matches!(self.data.sender, ActorSender::Local { .. })
     }
 
     /// Returns the total number of messages that have been sent to the actor regardless of
@@ -329,7 +745,7 @@ impl ActorId {
     /// FIXME Move these metrics to be retreived by via a system message because this won't work remote.
     pub fn sent(&self) -> usize {
         match &self.data.sender {
-            ActorSender::Local { sender, .. } => sender.sent(),
+            ActorSender::Local { mailbox, .. } => mailbox.total(|s| s.sent()),
             _ => panic!("Only implemented for Local sender!"),
         }
     }
@@ -340,7 +756,7 @@ impl ActorId {
     /// FIXME Move to be retreived by via a system message because this won't work remote.
     pub fn received(&self) -> usize {
         match &self.data.sender {
-            ActorSender::Local { sender, .. } => sender.received(),
+            ActorSender::Local { mailbox, .. } => mailbox.total(|s| s.received()),
             _ => panic!("Only implemented for Local sender!"),
         }
     }
@@ -350,7 +766,7 @@ impl ActorId {
     /// FIXME Move to be retreived by via a system message because this won't work remote.
     pub fn receivable(&self) -> usize {
         match &self.data.sender {
-            ActorSender::Local { sender, .. } => sender.receivable(),
+            ActorSender::Local { mailbox, .. } => mailbox.total(|s| s.receivable()),
             _ => panic!("Only implemented for Local sender!"),
         }
     }
@@ -360,7 +776,7 @@ impl ActorId {
     /// FIXME Move to be retreived by via a system message because this won't work remote.
     pub fn pending(&self) -> usize {
         match &self.data.sender {
-            ActorSender::Local { sender, .. } => sender.pending(),
+            ActorSender::Local { mailbox, .. } => mailbox.total(|s| s.pending()),
             _ => panic!("Only implemented for Local sender!"),
         }
     }
@@ -374,6 +790,14 @@ impl ActorId {
         }
     }
 
+    /// Returns the scheduling state of the local actor referenced by this [`ActorId`].
+    fn schedule_state(&self) -> &ScheduleState {
+        match &self.data.sender {
+            ActorSender::Local { schedule_state, .. } => schedule_state,
+            _ => panic!("Only implemented for Local sender!"),
+        }
+    }
+
     /// Marks the actor referenced by the [`ActorId`] as stopped and puts mechanisms in place to
     /// cause no more messages to be sent to the actor. Note that once stopped, an actor id can
     /// never be started again.
@@ -392,8 +816,8 @@ impl fmt::Debug for ActorId {
         write!(
             formatter,
             "ActorId{{id: {}, system_uuid: {}, name: {:?}, is_local: {}}}",
-            self.data.uuid.to_string(),
-            self.data.system_uuid.to_string(),
+            self.data.uuid,
+            self.data.system_uuid,
             self.data.name,
             self.is_local()
         )
@@ -415,16 +839,131 @@ impl Hash for ActorId {
     }
 }
 
+/// The shared state of a request sent with [`ActorId::ask`] that is completed when the reply
+/// arrives or the request times out.
+struct AskState {
+    /// The result of the request once it is complete.
+    result: Mutex<Option<Result<Message, ActorError>>>,
+    /// Signalled when the request is complete to wake up threads blocked in [`AskHandle::wait`].
+    condvar: Condvar,
+    /// The waker of the task polling the [`AskHandle`] as a future, if any.
+    waker: Mutex<Option<Waker>>,
+}
+
+impl AskState {
+    /// Completes the request with the `result` unless it was already completed.
+    fn complete(&self, result: Result<Message, ActorError>) {
+        {
+            let mut guard = self.result.lock().unwrap();
+            if guard.is_some() {
+                return;
+            }
+            *guard = Some(result);
+        }
+        self.condvar.notify_all();
+        if let Some(waker) = self.waker.lock().unwrap().take() {
+            waker.wake();
+        }
+    }
+}
+
+/// A handle to the reply of a request sent with [`ActorId::ask`].
+///
+/// The reply can be awaited by blocking the thread with [`AskHandle::wait`] or, as the handle
+/// implements [`Future`], by awaiting it in an async context. Note that blocking in
+/// [`AskHandle::wait`] from within an actor blocks the dispatcher thread running the actor.
+/// An awaited request is timed out by the timers of the actor system, so with a
+/// [`VirtualClock`] it only times out once the clock has been advanced past the timeout.
+pub struct AskHandle {
+    /// The actor system that correlates the reply with the request.
+    system: ActorSystem,
+    /// The id of the request.
+    request_id: Uuid,
+    /// The time at which the request times out.
+    deadline: Instant,
+    /// The state shared with the actor system.
+    state: Arc<AskState>,
+    /// The timer that times out the request once the handle has been polled as a future.
+    timer: Option<TimerHandle>,
+}
+
+impl AskHandle {
+    /// Blocks the calling thread until the reply arrives or the request times out.
+    pub fn wait(self) -> Result<Message, ActorError> {
+        let mut guard = self.state.result.lock().unwrap();
+        loop {
+            if let Some(result) = guard.take() {
+                return result;
+            }
+            let now = Instant::now();
+            if now >= self.deadline {
+                drop(guard);
+                self.system
+                    .complete_ask(&self.request_id, Err(ActorError::Timeout));
+                guard = self.state.result.lock().unwrap();
+            } else {
+                guard = self
+                    .state
+                    .condvar
+                    .wait_timeout(guard, self.deadline - now)
+                    .unwrap()
+                    .0;
+            }
+        }
+    }
+}
+
+impl Future for AskHandle {
+    type Output = Result<Message, ActorError>;
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<Self::Output> {
+        *self.state.waker.lock().unwrap() = Some(context.waker().clone());
+        if let Some(result) = self.state.result.lock().unwrap().take() {
+            return Poll::Ready(result);
+        }
+        if self.timer.is_none() {
+            // Nothing else would wake the task when the request times out. The timer only
+            // holds the state so that it doesn't keep the actor system alive.
+            let state = Arc::downgrade(&self.state);
+            let call = Box::new(move || {
+                if let Some(state) = state.upgrade() {
+                    state.complete(Err(ActorError::Timeout));
+                }
+            });
+            let delay = self.deadline.saturating_duration_since(Instant::now());
+            self.timer = Some(self.system.timers().add_call(call, delay));
+        }
+        Poll::Pending
+    }
+}
+
+impl Drop for AskHandle {
+    fn drop(&mut self) {
+        // Forget the request if nobody is waiting for the reply anymore.
+        if let Some(timer) = &self.timer {
+            timer.cancel();
+        }
+        self.system
+            .complete_ask(&self.request_id, Err(ActorError::Timeout));
+    }
+}
+
+impl fmt::Debug for AskHandle {
+    fn fmt(&self, formatter: &'_ mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "AskHandle{{request_id: {}}}", self.request_id)
+    }
+}
+
 /// A type for a function that processes messages for an actor.
 ///
 /// This will be passed to a spawn function to specify the handler used for managing the state of
 /// the actor based on the messages passed to the actor. The processor takes three arguments:
 /// * `state`   - A mutable reference to the current state of the actor.
 /// * `aid`     - The [`ActorId`] for this actor enclosed in an [`std::sync::Arc`]
-///               to allow access to the actor system for spawning, sending to self and so on.  
+///   to allow access to the actor system for spawning, sending to self and so on.
 /// * `message` - The current message to process in a reference to an [`std::sync::Arc`]. Note
-///               that messages are often shared amongst actors (sent to several actors at once)
-///               but their contents must be immutable to comply with the rules of an actor system.
+///   that messages are often shared amongst actors (sent to several actors at once)
+///   but their contents must be immutable to comply with the rules of an actor system.
 pub trait Processor<State: Send + Sync>:
     (FnMut(&mut State, ActorId, &Message) -> Status) + Send + Sync
 {
@@ -439,42 +978,81 @@ where
 }
 
 /// This is the internal type for the handler that will manage the state for the actor using the
-/// user-provided message processor.
-trait Handler: (FnMut(ActorId, &Message) -> Status) + Send + Sync + 'static {}
+/// user-provided message processor or the [`crate::lifecycle::Actor`] implementation.
+pub(crate) trait Handler: Send + Sync + 'static {
+    /// Processes a message sent to the actor.
+    fn handle(&mut self, aid: ActorId, message: &Message) -> Status;
+
+    /// Called once when the actor stopped for the given `reason`, after the last message it
+    /// processed. Processors have nothing to do here.
+    fn stopped(&mut self, _aid: ActorId, _reason: &StopReason) {}
+}
 
-impl<F> Handler for F where F: (FnMut(ActorId, &Message) -> Status) + Send + Sync + 'static {}
+impl<F> Handler for F
+where
+    F: (FnMut(ActorId, &Message) -> Status) + Send + Sync + 'static,
+{
+    fn handle(&mut self, aid: ActorId, message: &Message) -> Status {
+        self(aid, message)
+    }
+}
 
 /// An actual actor in the system. Please see overview and library documentation for more detail.
 struct Actor {
     /// Id of the associated actor.
     aid: ActorId,
-    /// Receiver for the actor channel.
-    receiver: SeccReceiver<Message>,
+    /// The message channels of the actor. They are shared with the [`ActorId`] of the actor
+    /// which needs them to drop the oldest message when a channel overflows.
+    mailbox: Arc<Mailbox>,
     /// The function that processes messages that are sent to the actor wrapped in a closure to
     /// erase the state type that the actor is managing. Note that this is in a mutex because the
     /// handler itself is `FnMut` and we also don't want there to be any possibility of two
     /// threads calling the handler concurrently as that would break the actor model rules.
     handler: Mutex<Box<dyn Handler>>,
+    /// The receive timeout of the actor or `None` if the actor has no receive timeout.
+    receive_timeout: Mutex<Option<ReceiveTimeout>>,
+    /// The threads that run the actor.
+    placement: Placement,
+    /// The reason the actor stopped until the handler is told about it. See
+    /// [`Actor::run_stop_hook`].
+    stop_reason: Mutex<Option<StopReason>>,
+}
+
+/// Where the messages of an actor are processed.
+enum Placement {
+    /// By the threads of the dispatcher with this index in the dispatchers of the actor system.
+    Dispatcher(usize),
+    /// By a thread of its own which parks while the actor has no messages. See
+    /// [`ActorBuilder::spawn_pinned`].
+    Pinned(thread::Thread),
+}
+
+/// Holds the receive timeout of an actor. See [`ActorSystem::set_receive_timeout`].
+struct ReceiveTimeout {
+    /// The timer that sends the timeout message. It stays armed while the actor has the
+    /// receive timeout and postpones itself while the actor keeps processing messages.
+    timer: TimerHandle,
+    /// The last time the actor processed a message, which the timer checks when it is due.
+    idle: Arc<Idle>,
+    /// The timeout message the timer sends. Any other timeout message, such as one sent by
+    /// hand or by a previous timer, is dropped unprocessed.
+    message: Message,
 }
 
 impl Actor {
-    /// Creates a new actor on the given actor system with the given processor function. The user
-    /// will pass the initial state of the actor as well as the processor that will be used to
-    /// process messages sent to the actor. The system and node id are passed separately because
-    /// of restrictions on mutex guards not being re-entrant in Rust.
-    pub fn new<F, State>(
+    /// Creates a new actor on the given actor system with the given handler, which is the
+    /// processor of the actor wrapped in a closure that holds or finds the state of the actor.
+    /// The system and node id are passed separately because of restrictions on mutex guards not
+    /// being re-entrant in Rust.
+    fn new(
         system_uuid: Uuid,
         name: Option<String>,
-        mut state: State,
-        mut processor: F,
-    ) -> Arc<Actor>
-    where
-        State: Send + Sync + 'static,
-        F: Processor<State> + 'static,
-    {
-        // FIXME: Issue #33: Let the user pass the size of the channel queue when creating.
-        // Create the channel for the actor.
-        let (sender, receiver) = secc::create::<Message>(32, 10);
+        config: ActorConfig,
+        placement: Placement,
+        handler: Box<dyn Handler>,
+    ) -> Arc<Actor> {
+        // Create the channels for the actor.
+        let mailbox = Arc::new(Mailbox::new(&config));
 
         // The sender will be put inside the actor id.
         let aid = ActorId {
@@ -484,64 +1062,225 @@ impl Actor {
                 name,
                 sender: ActorSender::Local {
                     stopped: AtomicBool::new(false),
-                    sender,
+                    mailbox: mailbox.clone(),
+                    overflow: config.overflow,
+                    schedule_state: ScheduleState::new(),
                 },
             }),
         };
 
-        // This handler will manage the state for the actor.
-        let handler = Box::new({
-            move |aid: ActorId, message: &Message| processor(&mut state, aid, message)
-        });
-
         // This is the receiving side of the actor which holds the processor wrapped in the
         // handler type.
         let actor = Actor {
             aid: aid.clone(),
-            receiver,
+            mailbox,
             handler: Mutex::new(handler),
+            receive_timeout: Mutex::new(None),
+            placement,
+            stop_reason: Mutex::new(None),
         };
 
         Arc::new(actor)
     }
 
-    /// This method is called to finish up the procedure for processing a message
-    ///
-    /// FIXME This should be converted to use a reductions system to process x number
-    /// of messages until a certain configurable time elapses to improve performance with
-    /// actors that get tons of super fast messages.
-    fn post_message_process(actor: &Arc<Self>) {
-        // We check to see if the actor still has pending messages and if so we re-schedule it
-        // for work at the back of the work channel. This prevents actors that get tons of
-        // messages from starving out actors that get few messages.
-        if actor.receiver.receivable() > 0 {
-            ActorSystem::current()
-                .data
-                .sender
-                .send_await(actor.clone())
-                .unwrap();
+    /// Tells the handler that the actor stopped if it did and the handler wasn't told yet. The
+    /// thread that stops the actor calls this but if another thread is processing a message at
+    /// the time, the handler is busy and that thread calls this once it is done with the
+    /// message instead. Either way the handler is told only once and after its last message.
+    fn run_stop_hook(actor: &Arc<Self>) {
+        if actor.stop_reason.lock().unwrap().is_none() {
+            return;
+        }
+        if let Ok(mut guard) = actor.handler.try_lock() {
+            let reason = actor.stop_reason.lock().unwrap().take();
+            if let Some(reason) = reason {
+                let stopped = panic::catch_unwind(AssertUnwindSafe(|| {
+                    guard.stopped(actor.aid.clone(), &reason)
+                }));
+                if let Err(payload) = stopped {
+                    let message_text = panic_message(&*payload);
+                    error!(
+                        "Actor {:?} panicked when stopped: {}",
+                        actor.aid, message_text
+                    );
+                }
+            }
+        }
+    }
+
+    /// This method is called to finish up the procedure for processing a batch of messages.
+    fn post_message_process(actor: &Arc<Self>, system: &ActorSystem) {
+        // The actor is marked idle before checking for messages so that a message sent in the
+        // meantime is noticed either here or by its sender. If the actor still has messages we
+        // re-schedule it for work at the back of the work queue. This prevents actors that get
+        // tons of messages from starving out actors that get few messages.
+        let schedule_state = actor.aid.schedule_state();
+        schedule_state.stop_running();
+        if !actor.aid.is_stopped() && actor.aid.receivable() > 0 && schedule_state.try_schedule() {
+            system.enqueue(actor.clone());
+        }
+    }
+
+    /// Replaces the receive timeout of the actor with one of the given `duration`, cancelling
+    /// the timer of the current one, or removes it if the `duration` is `None`.
+    fn set_receive_timeout(actor: &Arc<Self>, timers: &Arc<Timers>, duration: Option<Duration>) {
+        let mut receive_timeout = actor.receive_timeout.lock().unwrap();
+        if let Some(old) = receive_timeout.take() {
+            old.timer.cancel();
+        }
+        if let Some(duration) = duration {
+            let message = Message::new(SystemMsg::ReceiveTimeout);
+            let (timer, idle) = timers.add_idle(actor.aid.clone(), message.clone(), duration);
+            *receive_timeout = Some(ReceiveTimeout {
+                timer,
+                idle,
+                message,
+            });
+        }
+    }
+
+    /// Records that the actor processed a message, which restarts its receive timeout if it has
+    /// one.
+    fn touch_receive_timeout(actor: &Arc<Self>, timers: &Arc<Timers>) {
+        if let Some(receive_timeout) = &*actor.receive_timeout.lock().unwrap() {
+            receive_timeout.idle.touch(timers.now());
+        }
+    }
+
+    /// Checks whether the `message` is a receive timeout that was superseded because the actor
+    /// processed a message or changed its receive timeout after the timeout was sent.
+    fn is_stale_timeout(actor: &Arc<Self>, timers: &Arc<Timers>, message: &Message) -> bool {
+        match message.content_as::<SystemMsg>() {
+            Some(m) if *m == SystemMsg::ReceiveTimeout => {
+                match &*actor.receive_timeout.lock().unwrap() {
+                    Some(current) => {
+                        !current.message.ptr_eq(message) || current.idle.deadline() > timers.now()
+                    }
+                    None => true,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    /// Finishes processing the `message` from the lane with the given `index` by applying `op`
+    /// to its channel. The message is normally still the next receivable message in the channel
+    /// but it may have been dropped while it was being processed to make room for a newer
+    /// message when the overflow policy is [`OverflowPolicy::DropOldest`]. In that case
+    /// `dropped` is applied instead.
+    fn complete<F, G>(
+        actor: &Arc<Self>,
+        index: usize,
+        message: &Message,
+        op: F,
+        dropped: G,
+    ) -> Result<(), SeccErrors<Message>>
+    where
+        F: FnOnce(&SeccReceiver<Message>) -> Result<(), SeccErrors<Message>>,
+        G: FnOnce(&SeccReceiver<Message>) -> Result<(), SeccErrors<Message>>,
+    {
+        let receiver = actor.mailbox.lanes[index].receiver.lock().unwrap();
+        let is_next = match receiver.peek() {
+            Ok(next) => next.ptr_eq(message),
+            Err(_) => false,
+        };
+        if is_next {
+            op(&receiver)
+        } else {
+            dropped(&receiver)
         }
     }
 
-    /// Receive a message from the channel and process it with the actor. This function is the
-    /// core of the processing pipeline.
+    /// Pops the `message` from the lane with the given `index` once it is done being processed.
+    fn pop(actor: &Arc<Self>, index: usize, message: &Message) -> Result<(), SeccErrors<Message>> {
+        Actor::complete(actor, index, message, |r| r.pop(), |_| Ok(()))
+    }
+
+    /// Receives messages from the channel and processes them with the actor. This function is
+    /// the core of the processing pipeline. The actor keeps processing messages until it has
+    /// no more receivable messages or it reaches the limits on a batch set by
+    /// [`ActorSystemConfig::max_batch_messages`] and [`ActorSystemConfig::max_batch_micros`].
+    /// Processing a batch of messages saves busy actors a trip through the work channel for
+    /// every message while the limits keep them from starving out the other actors.
     fn receive(actor: Arc<Actor>) {
-        match actor.receiver.peek() {
-            Result::Err(err) => {
+        let system = ActorSystem::current();
+        let max_messages = system.data.config.max_batch_messages.max(1);
+        let max_time = Duration::from_micros(u64::from(system.data.config.max_batch_micros));
+        let start = Instant::now();
+        let mut processed: u16 = 0;
+        actor.aid.schedule_state().start_running();
+        while Actor::receive_one(&actor) {
+            processed += 1;
+            if processed >= max_messages
+                || start.elapsed() >= max_time
+                || actor.aid.receivable() == 0
+            {
+                break;
+            }
+        }
+        Actor::post_message_process(&actor, &system);
+    }
+
+    /// Receives the next message from the most urgent lane of the mailbox that has a receivable
+    /// message and processes it with the actor. Returns
+    /// whether the actor can go on to process another message, which it can't when it
+    /// stopped or there was no message to process.
+    fn receive_one(actor: &Arc<Actor>) -> bool {
+        // The message is cloned out of the channel, which only clones an `Arc`, so that the
+        // channel isn't locked while the message is processed.
+        let peeked = actor.mailbox.peek();
+        match peeked {
+            None => {
                 // This happening should be very rare but it would mean that the thread pool
                 // tried to process a message for an actor and was beaten to it by another
                 // thread. In this case we will just ignore the error and write out a debug
                 // message for purposes of later optimization.
-                warn!("receive(): No Message to process: {:?}", err);
-                ()
+                warn!("receive(): No Message to process for {:?}", actor.aid);
+                false
+            }
+            Some((index, message))
+                if Actor::is_stale_timeout(actor, ActorSystem::current().timers(), &message) =>
+            {
+                // The timeout no longer applies so it is dropped without bothering the actor.
+                if let Err(e) = Actor::pop(actor, index, &message) {
+                    error!("Error on pop(): {:?}.", e);
+                }
+                !actor.aid.is_stopped()
             }
-            Result::Ok(message) => {
+            Some((index, message)) => {
                 // In this case there is a message in the channel that we have to process through
                 // the actor. We process the message and then we may override the actor's returned
                 // value if its a Stop message. This is an allows actors that don't need to do
                 // anything special when stopping to ignore processing `Stop`.
+                // A panic in the processor is caught so that it only takes down the actor and not
+                // the dispatcher thread. The guard is held outside of the closure so that the
+                // mutex isn't poisoned by the unwinding.
+                // An actor stopped by another thread while this one waited for the handler isn't
+                // handed any more messages.
                 let mut guard = actor.handler.lock().unwrap();
-                let mut result = (&mut *guard)(actor.aid.clone(), message);
+                if actor.aid.is_stopped() {
+                    drop(guard);
+                    Actor::run_stop_hook(actor);
+                    return false;
+                }
+                let processed = panic::catch_unwind(AssertUnwindSafe(|| {
+                    guard.handle(actor.aid.clone(), &message)
+                }));
+                drop(guard);
+                Actor::run_stop_hook(actor);
+                let mut result = match processed {
+                    Ok(result) => result,
+                    Err(payload) => {
+                        let message_text = panic_message(&*payload);
+                        error!("Actor {:?} panicked: {}", actor.aid, message_text);
+                        ActorSystem::current()
+                            .stop_actor(actor.aid.clone(), StopReason::Panic(message_text));
+                        if let Err(e) = Actor::pop(actor, index, &message) {
+                            error!("Error on pop(): {:?}.", e);
+                        }
+                        return false;
+                    }
+                };
                 if let Some(m) = message.content_as::<SystemMsg>() {
                     if let SystemMsg::Stop = *m {
                         // Stop the actor anyway.
@@ -552,119 +1291,552 @@ impl Actor {
                 // Handle the result of the processing.
                 match result {
                     Status::Processed => {
-                        match actor.receiver.pop() {
+                        match Actor::pop(actor, index, &message) {
                             Ok(_) => (),
                             Err(e) => {
                                 error!("Error on pop(): {:?}.", e);
-                                ActorSystem::current().stop(actor.aid.clone())
+                                let reason = StopReason::ChannelError(format!("{:?}", e));
+                                ActorSystem::current().stop_actor(actor.aid.clone(), reason)
                             }
                         }
-                        Actor::post_message_process(&actor);
+                        Actor::touch_receive_timeout(actor, ActorSystem::current().timers());
+                        !actor.aid.is_stopped()
                     }
                     Status::Skipped => {
-                        match actor.receiver.skip() {
+                        match Actor::complete(actor, index, &message, |r| r.skip(), |_| Ok(())) {
                             Ok(_) => (),
                             Err(e) => {
                                 error!("Error on skip(): {:?}.", e);
-                                ActorSystem::current().stop(actor.aid.clone())
+                                let reason = StopReason::ChannelError(format!("{:?}", e));
+                                ActorSystem::current().stop_actor(actor.aid.clone(), reason)
                             }
                         }
-                        Actor::post_message_process(&actor);
+                        !actor.aid.is_stopped()
                     }
                     Status::ResetSkip => {
-                        match actor.receiver.pop_and_reset_skip() {
+                        // The messages skipped in the other lanes are receivable again as well.
+                        let reset = Actor::complete(
+                            actor,
+                            index,
+                            &message,
+                            |r| r.pop_and_reset_skip(),
+                            |r| r.reset_skip(),
+                        )
+                        .and_then(|_| actor.mailbox.reset_skip());
+                        match reset {
                             Ok(_) => (),
                             Err(e) => {
                                 error!("Error on pop_and_reset_skip(): {:?}.", e);
-                                ActorSystem::current().stop(actor.aid.clone())
+                                let reason = StopReason::ChannelError(format!("{:?}", e));
+                                ActorSystem::current().stop_actor(actor.aid.clone(), reason)
                             }
                         }
-                        Actor::post_message_process(&actor);
+                        Actor::touch_receive_timeout(actor, ActorSystem::current().timers());
+                        !actor.aid.is_stopped()
                     }
                     Status::Stop => {
-                        ActorSystem::current().stop(actor.aid.clone());
+                        ActorSystem::current().stop_actor(actor.aid.clone(), StopReason::Normal);
                         // Even though the actor is stopping we want to pop the message to make
                         // sure that the metrics on the actor's channel are correct. Then we will
                         // stop the actor in the actor system.
-                        match actor.receiver.pop() {
+                        match Actor::pop(actor, index, &message) {
                             Ok(_) => (),
                             Err(e) => {
                                 error!("Error on pop(): {:?}.", e);
-                                ActorSystem::current().stop(actor.aid.clone())
+                                let reason = StopReason::ChannelError(format!("{:?}", e));
+                                ActorSystem::current().stop_actor(actor.aid.clone(), reason)
                             }
                         }
+                        false
                     }
-                };
+                }
             }
         }
     }
 }
 
-/// Configuration structure for the Axiom actor system. Note that this configuration implements
-/// serde serialize and deserialize to allow users to read the config from any serde supported
-/// means.
-#[derive(Debug, Serialize, Deserialize)]
-pub struct ActorSystemConfig {
-    /// The number of slots to allocate for the work channel. This is the channel that the worker
-    /// threads use to schedule work on actors. The more traffic the actor system takes and the
-    /// longer the messages take to process, the bigger this should be. The default value is 100.
-    pub work_channel_size: u16,
-    /// The size of the thread pool which governs how many worker threads there are in the system.
-    /// The number of threads should be carefully considered to have sufficient concurrency but
-    /// not overschedule the CPU on the target hardware. The default value is 4.
-    pub thread_pool_size: u16,
-    /// Amount of time to wait in milliseconds between polling an empty work channel. The higher
-    /// this value is the longer threads will wait for polling and the kinder it will be to the
-    /// CPU. However, larger values will impact performance and may lead to some threads never
-    /// getting enough work to justify their existence. The default value is 10.
-    pub thread_wait_time: u16,
+/// Extracts the message from the `payload` of a panic, which is either a `&str` or a `String`
+/// when the panic was raised with `panic!`.
+pub(crate) fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Unknown panic payload".to_string()
+    }
 }
 
-impl ActorSystemConfig {
-    /// Create the config with the default values.
-    pub fn default() -> ActorSystemConfig {
-        ActorSystemConfig {
-            work_channel_size: 100,
-            thread_pool_size: 4,
-            thread_wait_time: 10,
+/// What happens to a message sent to an actor whose channel is full.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum OverflowPolicy {
+    /// The sender waits until there is room in the channel.
+    Block,
+    /// The send fails with an [`ActorError::ChannelFull`].
+    Fail,
+    /// The message being sent is dropped and the send succeeds.
+    DropNewest,
+    /// The oldest message in the channel that the actor can receive is dropped to make room
+    /// for the message being sent. If the actor skipped every message in the channel there is
+    /// nothing that can be dropped and the sender waits as with [`OverflowPolicy::Block`].
+    DropOldest,
+}
+
+/// The configuration of an actor that is given when the actor is spawned. See
+/// [`ActorSystem::spawn_with_config`].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ActorConfig {
+    /// The number of messages the channel of the actor can hold. Actors that get bursts of
+    /// messages need bigger channels while small channels save memory for the rest. The default
+    /// value is 32.
+    pub channel_size: u16,
+    /// The number of milliseconds that a sender waiting for room in the channel of the actor
+    /// waits before checking again. The default value is 10.
+    pub poll_time: u16,
+    /// What to do when a message is sent while the channel is full. The default value is
+    /// [`OverflowPolicy::Block`].
+    pub overflow: OverflowPolicy,
+    /// The name of the dispatcher that runs the actor. The default value is
+    /// [`DEFAULT_DISPATCHER`].
+    pub dispatcher: String,
+    /// The number of priority levels of user messages, from 0 up to one less than this, each
+    /// with a channel of its own. See [`ActorId::try_send_with_priority`]. System messages
+    /// always have a channel of their own ahead of the user messages. The default value is 1.
+    pub priority_levels: u8,
+}
+
+impl Default for ActorConfig {
+    fn default() -> ActorConfig {
+        ActorConfig {
+            channel_size: 32,
+            poll_time: 10,
+            overflow: OverflowPolicy::Block,
+            dispatcher: DEFAULT_DISPATCHER.to_string(),
+            priority_levels: 1,
         }
     }
 }
 
-/// Contains the inner data used by the actor system.
-struct ActorSystemData {
-    /// Unique version 4 UUID for this actor system.
-    uuid: Uuid,
-    /// The config for the actor system which was passed to it when created.
-    config: ActorSystemConfig,
-    /// Sender side of the work channel. When an actor gets a message and its pending count
-    /// goes from 0 to 1 it will put itself in the work channel via the sender. The actor will be
-    /// resent to the channel by a thread after handling a message if it has more messages
-    /// to process.
-    sender: Arc<SeccSender<Arc<Actor>>>,
-    /// Receiver side of the work channel. All threads in the pool will be grabbing actors
-    /// from this receiver to process messages.
-    receiver: Arc<SeccReceiver<Arc<Actor>>>,
-    /// Holds handles to the pool of threads processing the work channel.
-    thread_pool: Mutex<Vec<JoinHandle<()>>>,
-    /// A flag holding whether or not the system is currently shutting down.
-    shutdown_triggered: AtomicBool,
-    // Stores the number of running threads with a Condvar that will be used to notify anyone
-    // waiting on the condvar that all threads have exited.
-    running_thread_count: Arc<(Mutex<u16>, Condvar)>,
-    /// Holds the [`Actor`] objects keyed by the [`ActorId`]. The [`std::sync::RwLock`] will be
-    /// locked for write only when a new actor is spawned but otherwise will be locked for read
-    /// by the [`ActorId`] instances when they attempt to send an actor to the work channel for
-    /// processing.
-    actors_by_aid: Arc<RwLock<HashMap<ActorId, Arc<Actor>>>>,
-    /// Holds a map of the actor ids by the UUID in the actor id. UUIDs of actor ids are assigned
+/// A builder used to spawn an actor with options beyond its state and processor. The builder is
+/// obtained from [`ActorSystem::actor_builder`] and every option has a default so only the ones
+/// that matter need to be set.
+///
+/// # Examples
+/// ```
+/// use axiom::*;
+/// use std::time::Duration;
+///
+/// let system = ActorSystem::create(ActorSystemConfig::default());
+/// system.init_current();
+///
+/// let parent = system.spawn(0, |_: &mut usize, _: ActorId, _: &Message| Status::Processed);
+/// let aid = system
+///     .actor_builder()
+///     .name("worker")
+///     .channel_size(100)
+///     .overflow(OverflowPolicy::Fail)
+///     .parent(&parent)
+///     .monitored_by(&parent)
+///     .receive_timeout(Duration::from_secs(30))
+///     .spawn(0, |_: &mut usize, _: ActorId, _: &Message| Status::Processed)
+///     .unwrap();
+/// ```
+pub struct ActorBuilder {
+    /// The actor system the actor is spawned on.
+    system: ActorSystem,
+    /// The name of the actor, if it is named.
+    name: Option<String>,
+    /// The config of the actor.
+    config: ActorConfig,
+    /// The actor the spawned actor is linked to, if any.
+    parent: Option<ActorId>,
+    /// The actors that monitor the spawned actor.
+    monitors: Vec<ActorId>,
+    /// The receive timeout of the actor, if any.
+    receive_timeout: Option<Duration>,
+}
+
+impl ActorBuilder {
+    /// Sets the local name of the actor. Spawning fails with an [`ActorError::NameAlreadyUsed`]
+    /// if the name is taken.
+    pub fn name(mut self, name: &str) -> ActorBuilder {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    /// Sets the whole config of the actor, replacing any options set before.
+    pub fn config(mut self, config: ActorConfig) -> ActorBuilder {
+        self.config = config;
+        self
+    }
+
+    /// Sets the number of messages the channel of the actor can hold. See
+    /// [`ActorConfig::channel_size`].
+    pub fn channel_size(mut self, channel_size: u16) -> ActorBuilder {
+        self.config.channel_size = channel_size;
+        self
+    }
+
+    /// Sets how many milliseconds senders waiting for room in the channel wait between checks.
+    /// See [`ActorConfig::poll_time`].
+    pub fn poll_time(mut self, poll_time: u16) -> ActorBuilder {
+        self.config.poll_time = poll_time;
+        self
+    }
+
+    /// Sets what happens to messages sent while the channel of the actor is full. See
+    /// [`ActorConfig::overflow`].
+    pub fn overflow(mut self, overflow: OverflowPolicy) -> ActorBuilder {
+        self.config.overflow = overflow;
+        self
+    }
+
+    /// Sets the name of the dispatcher that runs the actor. Spawning fails with an
+    /// [`ActorError::UnknownDispatcher`] if the actor system has no such dispatcher. See
+    /// [`ActorConfig::dispatcher`].
+    pub fn dispatcher(mut self, name: &str) -> ActorBuilder {
+        self.config.dispatcher = name.to_string();
+        self
+    }
+
+    /// Sets the number of priority levels of the user messages sent to the actor. See
+    /// [`ActorConfig::priority_levels`].
+    pub fn priority_levels(mut self, priority_levels: u8) -> ActorBuilder {
+        self.config.priority_levels = priority_levels;
+        self
+    }
+
+    /// Links the actor to the `parent` as soon as it is spawned, before it is sent its start
+    /// message. See [`ActorSystem::link`].
+    pub fn parent(mut self, parent: &ActorId) -> ActorBuilder {
+        self.parent = Some(parent.clone());
+        self
+    }
+
+    /// Makes the `monitoring` actor monitor the actor as soon as it is spawned, before it is
+    /// sent its start message. This can be called several times to add several monitors. See
+    /// [`ActorSystem::monitor`].
+    pub fn monitored_by(mut self, monitoring: &ActorId) -> ActorBuilder {
+        self.monitors.push(monitoring.clone());
+        self
+    }
+
+    /// Sets the receive timeout of the actor. See [`ActorSystem::set_receive_timeout`].
+    pub fn receive_timeout(mut self, timeout: Duration) -> ActorBuilder {
+        self.receive_timeout = Some(timeout);
+        self
+    }
+
+    /// Spawns the actor using the given starting `state` and the `processor` function that will
+    /// be used to process its messages. The links, monitors and receive timeout are set up
+    /// before the actor is sent its start message so the actor can't stop without them.
+    pub fn spawn<F, State>(self, mut state: State, mut processor: F) -> Result<ActorId, ActorError>
+    where
+        State: Send + Sync + 'static,
+        F: Processor<State> + 'static,
+    {
+        let handler =
+            Box::new(move |aid: ActorId, message: &Message| processor(&mut state, aid, message));
+        self.spawn_handler(handler)
+    }
+
+    /// Spawns the actor with the given handler on the dispatcher set in the config.
+    pub(crate) fn spawn_handler(self, handler: Box<dyn Handler>) -> Result<ActorId, ActorError> {
+        let dispatcher = self.system.dispatcher_index(&self.config.dispatcher)?;
+        let actor = Actor::new(
+            self.system.data.uuid,
+            self.name.clone(),
+            self.config.clone(),
+            Placement::Dispatcher(dispatcher),
+            handler,
+        );
+        let aid = self.system.register_actor(actor)?;
+        self.start(aid)
+    }
+
+    /// Spawns the actor on a thread of its own that runs only this actor instead of on a
+    /// dispatcher, which is ignored. The thread calls `init` to create the starting state, so
+    /// the state doesn't have to be [`Send`] and can hold resources tied to the thread such as
+    /// handles from a C library or thread locals. The state is dropped on the thread when the
+    /// actor stops, at which point the thread exits. This waits for `init` to return and fails
+    /// with an [`ActorError::InitPanicked`] if it panics instead.
+    ///
+    /// # Examples
+    /// ```
+    /// use axiom::*;
+    /// use std::rc::Rc;
+    ///
+    /// let system = ActorSystem::create(ActorSystemConfig::default());
+    /// system.init_current();
+    ///
+    /// let aid = system
+    ///     .actor_builder()
+    ///     .name("pinned")
+    ///     .spawn_pinned(
+    ///         || Rc::new(0_usize),
+    ///         |_state: &mut Rc<usize>, _aid: ActorId, _message: &Message| Status::Processed,
+    ///     )
+    ///     .unwrap();
+    /// aid.send(Message::new(11));
+    /// ```
+    pub fn spawn_pinned<I, F, State>(self, init: I, mut processor: F) -> Result<ActorId, ActorError>
+    where
+        I: FnOnce() -> State + Send + 'static,
+        State: 'static,
+        F: (FnMut(&mut State, ActorId, &Message) -> Status) + Send + Sync + 'static,
+    {
+        let system = self.system.clone();
+        let (sender, receiver) = mpsc::channel::<Arc<Actor>>();
+        let (init_sender, init_receiver) = mpsc::channel::<Result<(), String>>();
+        let name = match &self.name {
+            Some(name) => format!("pinned-{}", name),
+            None => "pinned".to_string(),
+        };
+        let running = RunningThread::start(system.clone());
+        let thread = thread::Builder::new()
+            .name(name)
+            .spawn(move || {
+                let _running = running;
+                system.init_current();
+                match panic::catch_unwind(AssertUnwindSafe(init)) {
+                    Ok(state) => {
+                        PINNED_STATE.with(|cell| *cell.borrow_mut() = Some(Box::new(state)));
+                        init_sender.send(Ok(())).unwrap();
+                    }
+                    Err(payload) => {
+                        init_sender.send(Err(panic_message(&*payload))).unwrap();
+                        return;
+                    }
+                }
+                // The actor is sent once it is registered; if it can't be, the sender is dropped.
+                if let Ok(actor) = receiver.recv() {
+                    let wait_time =
+                        Duration::from_millis(system.data.config.thread_wait_time as u64);
+                    while !actor.aid.is_stopped() && !system.is_shutdown_triggered() {
+                        if actor.aid.schedule_state().is_scheduled() {
+                            Actor::receive(actor.clone());
+                        } else {
+                            thread::park_timeout(wait_time);
+                        }
+                    }
+                }
+                PINNED_STATE.with(|cell| cell.borrow_mut().take());
+            })
+            .expect("Unable to start a pinned actor thread");
+        if let Err(message) = init_receiver.recv().unwrap() {
+            return Err(ActorError::InitPanicked(message));
+        }
+
+        let handler = Box::new(move |aid: ActorId, message: &Message| {
+            PINNED_STATE.with(|cell| {
+                let mut guard = cell.borrow_mut();
+                let state = guard
+                    .as_mut()
+                    .and_then(|state| state.downcast_mut::<State>())
+                    .expect("Pinned actor run off of its thread");
+                processor(state, aid, message)
+            })
+        });
+        let actor = Actor::new(
+            self.system.data.uuid,
+            self.name.clone(),
+            self.config.clone(),
+            Placement::Pinned(thread.thread().clone()),
+            handler,
+        );
+        let aid = self.system.register_actor(actor.clone())?;
+        sender.send(actor).unwrap();
+        self.start(aid)
+    }
+
+    /// Sets up the links, monitors and receive timeout of the registered actor and then sends
+    /// it its start message.
+    fn start(self, aid: ActorId) -> Result<ActorId, ActorError> {
+        let system = self.system;
+        if let Some(parent) = &self.parent {
+            system.link(parent, &aid);
+        }
+        for monitoring in &self.monitors {
+            system.monitor(monitoring, &aid);
+        }
+        if self.receive_timeout.is_some() {
+            system.set_receive_timeout(&aid, self.receive_timeout);
+        }
+        aid.send(Message::new(SystemMsg::Start));
+        Ok(aid)
+    }
+}
+
+impl fmt::Debug for ActorBuilder {
+    fn fmt(&self, formatter: &'_ mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "ActorBuilder{{name: {:?}, config: {:?}}}",
+            self.name, self.config
+        )
+    }
+}
+
+/// Configuration structure for the Axiom actor system. Note that this configuration implements
+/// serde serialize and deserialize to allow users to read the config from any serde supported
+/// means.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ActorSystemConfig {
+    /// The number of slots to allocate for the work channel. This is the channel that the worker
+    /// threads use to schedule work on actors. The more traffic the actor system takes and the
+    /// longer the messages take to process, the bigger this should be. The default value is 100.
+    pub work_channel_size: u16,
+    /// The size of the thread pool which governs how many worker threads there are in the system.
+    /// The number of threads should be carefully considered to have sufficient concurrency but
+    /// not overschedule the CPU on the target hardware. The default value is 4.
+    pub thread_pool_size: u16,
+    /// Amount of time to wait in milliseconds between checks for shutdown by the background
+    /// threads of the actor system, such as the timer thread and the threads serving
+    /// connections to other actor systems. The dispatcher threads don't poll; they park while
+    /// the work channel is empty and are woken up when there is work or the system shuts down.
+    /// This is also how long an actor waits between checks for room in a full work channel.
+    /// The default value is 10.
+    pub thread_wait_time: u16,
+    /// The address that the actor system will listen on for connections from other actor
+    /// systems, such as `"0.0.0.0:7717"`. If this is `None` the actor system will not accept
+    /// connections but can still connect to other actor systems. The default value is `None`.
+    pub listen_address: Option<String>,
+    /// Addresses of actor systems to connect to when this actor system starts. The actor system
+    /// will keep trying to connect to any seed that it is not connected to and will learn about
+    /// the other members of the cluster from the seeds. The default value is empty.
+    pub seed_nodes: Vec<String>,
+    /// Amount of time in milliseconds between heartbeats sent to connected actor systems. This
+    /// is also how often the seed nodes are retried. The default value is 1000.
+    pub heartbeat_interval: u16,
+    /// Amount of time in milliseconds that a connected actor system can go without sending
+    /// anything before its connection is considered lost and closed. This should be several
+    /// times the `heartbeat_interval`. The default value is 5000.
+    pub heartbeat_timeout: u16,
+    /// The maximum number of messages that an actor processes each time a dispatcher thread
+    /// takes it from the work channel before it goes to the back of the work channel again.
+    /// Larger batches save busy actors trips through the work channel but let them hold on to
+    /// a thread for longer. A value of 1 processes a single message per turn. The default
+    /// value is 100.
+    pub max_batch_messages: u16,
+    /// The maximum amount of time in microseconds that an actor keeps processing a batch of
+    /// messages. The actor finishes the message it is processing when the time runs out so a
+    /// slow message can take longer. The default value is 1000.
+    pub max_batch_micros: u32,
+    /// The scheduler that hands actors with messages to the dispatcher threads. See
+    /// [`SchedulerKind`] for the choices. The default value is [`SchedulerKind::GlobalQueue`].
+    pub scheduler: SchedulerKind,
+    /// The dispatchers the actor system has in addition to the default one, which uses the
+    /// `thread_pool_size`, `scheduler` and `work_channel_size` above. Actors choose their
+    /// dispatcher by name when they are spawned. The default value is empty.
+    pub dispatchers: Vec<DispatcherConfig>,
+    /// The format that the content of messages sent to other actor systems is encoded in. All
+    /// of the actor systems in a cluster have to use the same codec. The default value is
+    /// [`Codec::Bincode`].
+    pub codec: Codec,
+}
+
+impl ActorSystemConfig {
+    /// Create the config with the default values.
+    #[allow(clippy::should_implement_trait)]
+    pub fn default() -> ActorSystemConfig {
+        ActorSystemConfig {
+            work_channel_size: 100,
+            thread_pool_size: 4,
+            thread_wait_time: 10,
+            listen_address: None,
+            seed_nodes: Vec::new(),
+            heartbeat_interval: 1000,
+            heartbeat_timeout: 5000,
+            max_batch_messages: 100,
+            max_batch_micros: 1000,
+            scheduler: SchedulerKind::GlobalQueue,
+            dispatchers: Vec::new(),
+            codec: Codec::default(),
+        }
+    }
+
+    /// Checks that an actor system can be created with the config, which isn't the case when
+    /// two of its dispatchers, counting the default dispatcher, have the same name.
+    ///
+    /// # Examples
+    /// ```
+    /// use axiom::*;
+    ///
+    /// let config = ActorSystemConfig {
+    ///     dispatchers: vec![DispatcherConfig::new("io", 1), DispatcherConfig::new("io", 2)],
+    ///     ..ActorSystemConfig::default()
+    /// };
+    /// assert_eq!(
+    ///     Err(ActorError::DuplicateDispatcher("io".to_string())),
+    ///     config.validate()
+    /// );
+    /// ```
+    pub fn validate(&self) -> Result<(), ActorError> {
+        let mut names = vec![DEFAULT_DISPATCHER];
+        for dispatcher in &self.dispatchers {
+            if names.contains(&dispatcher.name.as_str()) {
+                return Err(ActorError::DuplicateDispatcher(dispatcher.name.clone()));
+            }
+            names.push(&dispatcher.name);
+        }
+        Ok(())
+    }
+}
+
+/// Contains the inner data used by the actor system.
+struct ActorSystemData {
+    /// Unique version 4 UUID for this actor system.
+    uuid: Uuid,
+    /// The config for the actor system which was passed to it when created.
+    config: ActorSystemConfig,
+    /// The dispatchers with the schedulers that hand actors to the dispatcher threads, starting
+    /// with the default dispatcher. When an idle actor gets a message it is scheduled on its
+    /// dispatcher. The actor will be scheduled again by a thread after handling a batch of
+    /// messages if it has more messages to process.
+    dispatchers: Vec<Dispatcher<Arc<Actor>>>,
+    /// Holds handles to the threads of all of the dispatchers.
+    thread_pool: Mutex<Vec<JoinHandle<()>>>,
+    /// A flag holding whether or not the system is currently shutting down.
+    shutdown_triggered: AtomicBool,
+    // Stores the number of running threads with a Condvar that will be used to notify anyone
+    // waiting on the condvar that all threads have exited.
+    running_thread_count: Arc<(Mutex<u16>, Condvar)>,
+    /// Holds the [`Actor`] objects keyed by the [`ActorId`]. The [`std::sync::RwLock`] will be
+    /// locked for write only when a new actor is spawned but otherwise will be locked for read
+    /// by the [`ActorId`] instances when they attempt to send an actor to the work channel for
+    /// processing.
+    actors_by_aid: Arc<RwLock<HashMap<ActorId, Arc<Actor>>>>,
+    /// Holds a map of the actor ids by the UUID in the actor id. UUIDs of actor ids are assigned
     /// when an actor is spawned using version 4 UUIDs.
     aids_by_uuid: Arc<RwLock<HashMap<Uuid, ActorId>>>,
     /// Holds a map of user assigned names to actor ids set when the actors were spawned.
     aids_by_name: Arc<RwLock<HashMap<String, ActorId>>>,
+    /// Holds the names registered across the cluster. Every member keeps a replica of the map.
+    /// See [`ActorSystem::register_global`].
+    global_names: Arc<RwLock<HashMap<String, ActorId>>>,
+    /// Holds the links of the local actors where the key is the `aid` of a local actor and the
+    /// value is the set of `aid`s, local or remote, that the actor is linked to.
+    links: Arc<RwLock<HashMap<ActorId, HashSet<ActorId>>>>,
+    /// Holds the `aid`s of the local actors that trap exits.
+    trapping_exits: Arc<RwLock<HashSet<ActorId>>>,
     /// Holds a map of monitors where the key is the `aid` of the actor being monitored and
     /// the value is a vector of `aid`s that are monitoring the actor.
     monitoring_by_monitored: Arc<RwLock<HashMap<ActorId, Vec<ActorId>>>>,
+    /// Holds the connections to remote actor systems keyed by the UUID of the remote actor
+    /// system. This is also the membership of the cluster as seen by this actor system.
+    /// Messages sent to remote actors are relayed over these connections.
+    connections: Arc<RwLock<HashMap<Uuid, Arc<Connection>>>>,
+    /// The address the actor system is listening on, if it is listening. This is the address
+    /// that is advertised to other members of the cluster.
+    listen_address: RwLock<Option<SocketAddr>>,
+    /// Holds the `aid`s of the actors that are informed when actor systems join or leave the
+    /// cluster.
+    node_monitors: Arc<RwLock<Vec<ActorId>>>,
+    /// Holds the requests sent with [`ActorId::ask`] that are waiting for a reply, keyed by
+    /// the id of the request.
+    pending_asks: Mutex<HashMap<Uuid, Arc<AskState>>>,
+    /// The timers that send messages to actors after a delay or periodically.
+    timers: Arc<Timers>,
 }
 
 /// An actor system that contains and manages the actors spawned inside it.
@@ -680,27 +1852,59 @@ impl ActorSystem {
     /// Creates an actor system with the given config. The user should benchmark how
     /// many slots in the work channel, the number of threads they need and so on in order
     /// to satisfy the requirements of the software they are creating.
+    ///
+    /// # Panics
+    /// Panics if the config is invalid, see [`ActorSystemConfig::validate`].
     pub fn create(config: ActorSystemConfig) -> ActorSystem {
-        let (sender, receiver) =
-            secc::create_with_arcs::<Arc<Actor>>(config.work_channel_size, config.thread_wait_time);
+        ActorSystem::create_with_clock(config, Arc::new(SystemClock))
+    }
+
+    /// Creates an actor system with the given config whose timers read the time from the given
+    /// `clock`. This is mostly useful for tests that pass a [`VirtualClock`] to control when
+    /// the timers fire.
+    ///
+    /// # Panics
+    /// Panics if the config is invalid, see [`ActorSystemConfig::validate`].
+    pub fn create_with_clock(config: ActorSystemConfig, clock: Arc<dyn Clock>) -> ActorSystem {
+        if let Err(e) = config.validate() {
+            panic!("Invalid actor system config: {:?}", e);
+        }
+        let default_dispatcher = DispatcherConfig {
+            name: DEFAULT_DISPATCHER.to_string(),
+            thread_pool_size: config.thread_pool_size,
+            scheduler: config.scheduler,
+            work_channel_size: config.work_channel_size,
+        };
+        let dispatchers: Vec<Dispatcher<Arc<Actor>>> = std::iter::once(&default_dispatcher)
+            .chain(&config.dispatchers)
+            .map(|dispatcher| Dispatcher::new(dispatcher, config.thread_wait_time))
+            .collect();
 
-        let thread_pool = Mutex::new(Vec::with_capacity(config.thread_pool_size as usize));
-        let running_thread_count = Arc::new((Mutex::new(config.thread_pool_size), Condvar::new()));
+        let thread_count: u16 = dispatchers.iter().map(|d| d.thread_pool_size).sum();
+        let thread_pool = Mutex::new(Vec::with_capacity(thread_count as usize));
+        let running_thread_count = Arc::new((Mutex::new(thread_count), Condvar::new()));
 
         // Creates the actor system with the thread pools and actor map initialized.
         let system = ActorSystem {
             data: Arc::new(ActorSystemData {
                 uuid: Uuid::new_v4(),
-                config: config,
-                sender,
-                receiver,
+                config,
+                dispatchers,
                 thread_pool,
                 shutdown_triggered: AtomicBool::new(false),
                 running_thread_count,
                 actors_by_aid: Arc::new(RwLock::new(HashMap::new())),
                 aids_by_uuid: Arc::new(RwLock::new(HashMap::new())),
                 aids_by_name: Arc::new(RwLock::new(HashMap::new())),
+                global_names: Arc::new(RwLock::new(HashMap::new())),
+                links: Arc::new(RwLock::new(HashMap::new())),
+                trapping_exits: Arc::new(RwLock::new(HashSet::new())),
                 monitoring_by_monitored: Arc::new(RwLock::new(HashMap::new())),
+                connections: Arc::new(RwLock::new(HashMap::new())),
+                listen_address: RwLock::new(None),
+                node_monitors: Arc::new(RwLock::new(Vec::new())),
+                pending_asks: Mutex::new(HashMap::new()),
+                timers: Timers::new(clock),
             }),
         };
 
@@ -709,46 +1913,64 @@ impl ActorSystem {
         // get around rust borrow constraints without unnecessarily copying things.
         {
             let mut guard = system.data.thread_pool.lock().unwrap();
-            for _ in 0..system.data.config.thread_pool_size {
-                let thread = system.start_dispatcher_thread();
-                guard.push(thread);
+            for (dispatcher, d) in system.data.dispatchers.iter().enumerate() {
+                for index in 0..d.thread_pool_size {
+                    let thread = system.start_dispatcher_thread(dispatcher, index as usize);
+                    guard.push(thread);
+                }
+            }
+        }
+
+        // If the actor system is configured to be part of a cluster we start listening and
+        // start the thread that maintains the connections to the other members.
+        if let Some(address) = &system.data.config.listen_address {
+            if let Err(e) = system.listen(address) {
+                error!("Unable to listen on {}: {:?}", address, e);
             }
         }
+        start_heartbeat(system.clone());
+        start_timer_thread(
+            system.clone(),
+            system.data.timers.clone(),
+            system.data.config.thread_wait_time,
+        );
 
         system
     }
 
     /// Starts a thread for the dispatcher that will process actor messages. The dispatcher
-    /// threads constantly grab at the work channel trying to get the next actor off the channel.
-    /// When they get an actor they will process the message using the actor and then check to see
-    /// if the actor has more receivable messages. If it does then the actor will be re-sent to
-    /// the work channel to process the next message. This process allows thousands of actors to
-    /// run and not take up resources if they have no messages to process but also prevents one
-    /// super busy actor from starving out other actors that get messages only occasionally.
-    fn start_dispatcher_thread(&self) -> JoinHandle<()> {
+    /// threads constantly take the next scheduled actor from the scheduler. When they get an
+    /// actor they will process its messages and then check to see if the actor has more
+    /// receivable messages. If it does then the actor will be scheduled again to process the
+    /// next messages. This process allows thousands of actors to run and not take up resources
+    /// if they have no messages to process but also prevents one super busy actor from starving
+    /// out other actors that get messages only occasionally. When there is no work the thread
+    /// parks until an actor is scheduled or the actor system shuts down. The `dispatcher` is the
+    /// index of the dispatcher the thread belongs to and the `index` is the position of the
+    /// thread in the pool of that dispatcher. The thread is named after both.
+    fn start_dispatcher_thread(&self, dispatcher: usize, index: usize) -> JoinHandle<()> {
         // FIXME Issue #32: Add metrics to this to log warnings if the messages take to long to process.
         // FIXME Issue #32: Add metrics to this to log a warning if messages or actors are spending too
         // long in the channel.
         let system = self.clone();
-        let receiver = self.data.receiver.clone();
-        let thread_timeout = self.data.config.thread_wait_time;
+        let name = format!("{}-{}", self.data.dispatchers[dispatcher].name, index);
 
-        thread::spawn(move || {
+        let spawned = thread::Builder::new().name(name).spawn(move || {
             system.init_current();
-            while !system.data.shutdown_triggered.load(Ordering::Relaxed) {
-                match receiver.receive_await_timeout(thread_timeout) {
-                    Err(_) => (), // not an error, just loop and try again.
-                    Ok(actor) => Actor::receive(actor),
-                }
+            let scheduler = &system.data.dispatchers[dispatcher].scheduler;
+            scheduler.attach(index);
+            while let Some(actor) = scheduler.next(index) {
+                Actor::receive(actor);
             }
             let (mutex, condvar) = &*system.data.running_thread_count;
             let mut count = mutex.lock().unwrap();
-            *count = *count - 1;
+            *count -= 1;
             // If this is the last thread exiting we will notify any waiters.
             if *count == 0 {
                 condvar.notify_all();
             }
-        })
+        });
+        spawned.expect("Unable to start a dispatcher thread")
     }
 
     /// Initialises this actor system to use for the current thread which is necessary if the
@@ -772,18 +1994,65 @@ impl ActorSystem {
         })
     }
 
-    /// Triggers a shutdown but doesn't wait for threads to stop.
+    /// Fetches the actor system for the current thread if the thread has one.
+    pub(crate) fn try_current() -> Option<ActorSystem> {
+        ACTOR_SYSTEM.with(|actor_system| actor_system.get().cloned())
+    }
+
+    /// The unique UUID of this actor system.
+    #[inline]
+    pub fn uuid(&self) -> Uuid {
+        self.data.uuid
+    }
+
+    /// The config that the actor system was created with.
+    pub fn config(&self) -> &ActorSystemConfig {
+        &self.data.config
+    }
+
+    /// Triggers a shutdown but doesn't wait for threads to stop. All actors are stopped with
+    /// a [`StopReason::SystemShutdown`] and any connections to remote actor systems are closed.
     pub fn trigger_shutdown(&self) {
         self.data.shutdown_triggered.store(true, Ordering::Relaxed);
+        for dispatcher in &self.data.dispatchers {
+            dispatcher.scheduler.shutdown();
+        }
+
+        // The actors are stopped so that remote actors monitoring them learn the reason.
+        let aids: Vec<ActorId> = {
+            let actors_by_aid = self.data.actors_by_aid.read().unwrap();
+            actors_by_aid.keys().cloned().collect()
+        };
+        for aid in aids {
+            self.stop_actor(aid, StopReason::SystemShutdown);
+        }
+
+        let connections = self.data.connections.read().unwrap();
+        for connection in connections.values() {
+            connection.close();
+        }
+    }
+
+    /// Returns the timer service of the actor system.
+    pub(crate) fn timers(&self) -> &Arc<Timers> {
+        &self.data.timers
+    }
+
+    /// Determines if the actor system has been told to shut down.
+    pub(crate) fn is_shutdown_triggered(&self) -> bool {
+        self.data.shutdown_triggered.load(Ordering::Relaxed)
     }
 
     /// Awaits for the actor system to be shutdown using a relatively CPU minimal condvar as
     /// a signalling mechanism. This function will block until all actor system threads have
     /// stopped.
     pub fn await_shutdown(&self) {
-        let &(ref mutex, ref condvar) = &*self.data.running_thread_count;
-        let guard = mutex.lock().unwrap();
-        let _condvar_guard = condvar.wait(guard).unwrap();
+        let (mutex, condvar) = &*self.data.running_thread_count;
+        let mut guard = mutex.lock().unwrap();
+        // The threads may have already exited in which case there is nothing to wait for.
+        while *guard > 0 {
+            guard = condvar.wait(guard).unwrap();
+        }
     }
 
     /// Triggers a shutdown of the system and returns only when all threads have joined.
@@ -792,19 +2061,41 @@ impl ActorSystem {
         self.await_shutdown();
     }
 
-    /// Returns the total number of times actors have been sent to the work channel.
+    /// Returns the total number of times actors have been scheduled.
     pub fn sent(&self) -> usize {
-        self.data.receiver.sent()
+        self.data
+            .dispatchers
+            .iter()
+            .map(|d| d.scheduler.sent())
+            .sum()
     }
 
-    /// Returns the total number of times actors have been processed from the work channel.
+    /// Returns the total number of times actors have been taken by the dispatcher threads.
     pub fn received(&self) -> usize {
-        self.data.receiver.received()
+        self.data
+            .dispatchers
+            .iter()
+            .map(|d| d.scheduler.received())
+            .sum()
     }
 
-    /// Returns the total number of actors that are currently pending in the work channel.
+    /// Returns the total number of actors that are currently scheduled and waiting for a
+    /// dispatcher thread.
     pub fn pending(&self) -> usize {
-        self.data.receiver.pending()
+        self.data
+            .dispatchers
+            .iter()
+            .map(|d| d.scheduler.pending())
+            .sum()
+    }
+
+    /// Returns the index of the dispatcher with the given `name`.
+    fn dispatcher_index(&self, name: &str) -> Result<usize, ActorError> {
+        self.data
+            .dispatchers
+            .iter()
+            .position(|d| d.name == name)
+            .ok_or_else(|| ActorError::UnknownDispatcher(name.to_string()))
     }
 
     // A internal helper to register an actor in the actor system.
@@ -849,10 +2140,45 @@ impl ActorSystem {
         State: Send + Sync + 'static,
         F: Processor<State> + 'static,
     {
-        let actor = Actor::new(self.data.uuid, None, state, processor);
-        let result = self.register_actor(actor).unwrap();
-        result.send(Message::new(SystemMsg::Start));
-        result
+        self.actor_builder().spawn(state, processor).unwrap()
+    }
+
+    /// Spawns a new unnamed actor as with [`ActorSystem::spawn`] but using the given `config`
+    /// for the actor.
+    ///
+    /// # Examples
+    /// ```
+    /// use axiom::*;
+    ///
+    /// let system = ActorSystem::create(ActorSystemConfig::default());
+    /// system.init_current();
+    ///
+    /// let config = ActorConfig {
+    ///     channel_size: 1000,
+    ///     overflow: OverflowPolicy::DropOldest,
+    ///     ..ActorConfig::default()
+    /// };
+    /// let aid = system.spawn_with_config(
+    ///     config,
+    ///     0 as usize,
+    ///     |_state: &mut usize, _aid: ActorId, _message: &Message| Status::Processed,
+    /// );
+    /// aid.send(Message::new(11));
+    /// ```
+    pub fn spawn_with_config<F, State>(
+        &self,
+        config: ActorConfig,
+        state: State,
+        processor: F,
+    ) -> ActorId
+    where
+        State: Send + Sync + 'static,
+        F: Processor<State> + 'static,
+    {
+        self.actor_builder()
+            .config(config)
+            .spawn(state, processor)
+            .unwrap()
     }
 
     /// Spawns a new named actor on the `system` using the given starting `state` for the actor
@@ -886,27 +2212,49 @@ impl ActorSystem {
         State: Send + Sync + 'static,
         F: Processor<State> + 'static,
     {
-        let actor = Actor::new(self.data.uuid, Some(name.to_string()), state, processor);
-        let result = self.register_actor(actor)?;
-        result.send(Message::new(SystemMsg::Start));
-        Ok(result)
+        self.actor_builder().name(name).spawn(state, processor)
+    }
+
+    /// Spawns a new named actor as with [`ActorSystem::spawn_named`] but using the given
+    /// `config` for the actor.
+    pub fn spawn_named_with_config<F, State>(
+        &self,
+        name: &str,
+        config: ActorConfig,
+        state: State,
+        processor: F,
+    ) -> Result<ActorId, ActorError>
+    where
+        State: Send + Sync + 'static,
+        F: Processor<State> + 'static,
+    {
+        self.actor_builder()
+            .name(name)
+            .config(config)
+            .spawn(state, processor)
+    }
+
+    /// Returns a builder to spawn an actor with more options than the other spawn functions
+    /// take. See [`ActorBuilder`].
+    pub fn actor_builder(&self) -> ActorBuilder {
+        ActorBuilder {
+            system: self.clone(),
+            name: None,
+            config: ActorConfig::default(),
+            parent: None,
+            monitors: Vec::new(),
+            receive_timeout: None,
+        }
     }
 
     /// Schedules the `aid` for work. Note that this is the only time that we have to use the
-    /// lookup table. This function gets called when an actor goes from 0 receivable messages to
-    /// 1 receivable message. If the actor has more receivable messages then this will not be
-    /// needed to be called because the dispatcher threads will handle the process of resending
-    /// the actor to the work channel.
-    ///
-    /// TODO Put tests verifying the resend on multiple messages.
+    /// lookup table. This function gets called when a sender moves an idle actor to scheduled.
+    /// Actors that are already scheduled or running don't need this because the dispatcher
+    /// threads will handle the process of resending the actor to the work queue.
     fn schedule(&self, aid: ActorId) {
         let actors_by_aid = self.data.actors_by_aid.read().unwrap();
         match actors_by_aid.get(&aid) {
-            Some(actor) => self
-                .data
-                .sender
-                .send(actor.clone())
-                .expect("Unable to Schedule actor: "),
+            Some(actor) => self.enqueue(actor.clone()),
             None => {
                 // The actor was removed from the map so ignore the problem and just log
                 // a warning.
@@ -916,11 +2264,19 @@ impl ActorSystem {
                     aid.clone(),
                     self.data.uuid.to_string(),
                 );
-                ()
             }
         }
     }
 
+    /// Hands the `actor` to the scheduler of its dispatcher so that one of the threads of the
+    /// dispatcher processes it or, if the actor is pinned, wakes up its thread.
+    fn enqueue(&self, actor: Arc<Actor>) {
+        match &actor.placement {
+            Placement::Dispatcher(index) => self.data.dispatchers[*index].scheduler.schedule(actor),
+            Placement::Pinned(thread) => thread.unpark(),
+        }
+    }
+
     /// Stops an actor by shutting down its channels and removing it from the actors list and
     /// telling the actor id to not allow messages to be sent to the actor since the receiving
     /// side of the actor is gone.
@@ -928,20 +2284,61 @@ impl ActorSystem {
     /// This is something that should rarely be called from the outside as it is much better to
     /// send the actor a [`SystemMsg::Stop`] message and allow it to stop gracefully.
     pub fn stop(&self, aid: ActorId) {
-        {
+        self.stop_actor(aid, StopReason::Forced);
+    }
+
+    /// Stops the actor as described in [`ActorSystem::stop`] and informs the monitoring actors
+    /// of the `reason` the actor stopped. When the actor system is shutting down only remote
+    /// monitors are informed since the local ones are being shut down as well.
+    fn stop_actor(&self, aid: ActorId, reason: StopReason) {
+        let actor = {
             let mut actors_by_aid = self.data.actors_by_aid.write().unwrap();
             let mut aids_by_uuid = self.data.aids_by_uuid.write().unwrap();
             let mut aids_by_name = self.data.aids_by_name.write().unwrap();
-            actors_by_aid.remove(&aid);
+            let actor = actors_by_aid.remove(&aid);
             aids_by_uuid.remove(&aid.uuid());
             if let Some(name_string) = aid.name() {
                 aids_by_name.remove(&name_string);
             }
             aid.stop();
+            actor
+        };
+        if let Some(actor) = &actor {
+            *actor.stop_reason.lock().unwrap() = Some(reason.clone());
+            Actor::run_stop_hook(actor);
+            // The thread of a pinned actor is woken up so that it exits right away.
+            if let Placement::Pinned(thread) = &actor.placement {
+                thread.unpark();
+            }
+        }
+        self.data.timers.cancel_all(&aid);
+
+        // Release any global names held by the actor and tell the rest of the cluster.
+        let released: Vec<String> = {
+            let mut global_names = self.data.global_names.write().unwrap();
+            let names: Vec<String> = global_names
+                .iter()
+                .filter(|(_, v)| **v == aid)
+                .map(|(k, _)| k.clone())
+                .collect();
+            for name in &names {
+                global_names.remove(name);
+            }
+            names
+        };
+        for name in released {
+            broadcast(
+                self,
+                &WireMessage::GlobalUnregister {
+                    name,
+                    aid: aid.clone(),
+                },
+            );
         }
 
         // Notify all of the actors monitoring the actor that is stopped and remove the
-        // actor from the map of monitors.
+        // actor from the map of monitors. Remote actor systems are told only once that the
+        // actor stopped and will notify their own monitoring actors.
         if let Some(monitoring) = self
             .data
             .monitoring_by_monitored
@@ -949,10 +2346,38 @@ impl ActorSystem {
             .unwrap()
             .remove(&aid)
         {
+            let mut remote_systems: HashSet<Uuid> = HashSet::new();
             for m_aid in monitoring {
-                ActorId::send(&m_aid, Message::new(SystemMsg::Stopped(aid.clone())));
+                if !m_aid.is_local() {
+                    remote_systems.insert(m_aid.system_uuid());
+                } else if reason != StopReason::SystemShutdown {
+                    // The monitoring actor may have stopped itself in the meantime.
+                    let _ = m_aid.try_send(Message::new(SystemMsg::Stopped {
+                        aid: aid.clone(),
+                        reason: reason.clone(),
+                    }));
+                }
+            }
+            for system_uuid in remote_systems {
+                let wire_message = WireMessage::Stopped {
+                    aid: aid.clone(),
+                    reason: reason.clone(),
+                };
+                if let Err(e) = self.send_to_system(&system_uuid, &wire_message) {
+                    warn!(
+                        "Unable to tell {} that {:?} stopped: {:?}",
+                        system_uuid, aid, e
+                    );
+                }
             }
         }
+
+        // Inform the linked actors, which may stop them as well.
+        self.data.trapping_exits.write().unwrap().remove(&aid);
+        let linked = self.data.links.write().unwrap().remove(&aid);
+        for l_aid in linked.unwrap_or_default() {
+            self.deliver_exit(&l_aid, &aid, &reason);
+        }
     }
 
     /// Checks to see if the actor with the given [`ActorId`] is alive within this actor system.
@@ -965,63 +2390,743 @@ impl ActorSystem {
     /// `aid` in a [`Option::Some`] or [`Option::None`] if not found.
     pub fn find_aid_by_uuid(&self, uuid: &Uuid) -> Option<ActorId> {
         let aids_by_uuid = self.data.aids_by_uuid.read().unwrap();
-        aids_by_uuid.get(uuid).map(|aid| aid.clone())
+        aids_by_uuid.get(uuid).cloned()
     }
 
     /// Look up an [`ActorId`] by the user assigned name of the actor and either returns the
     /// located `aid` in a [`Option::Some`] or [`Option::None`] if not found.
     pub fn find_aid_by_name(&self, name: &str) -> Option<ActorId> {
         let aids_by_name = self.data.aids_by_name.read().unwrap();
-        aids_by_name.get(&name.to_string()).map(|aid| aid.clone())
+        aids_by_name.get(&name.to_string()).cloned()
     }
 
-    /// Adds a monitor so that `monitoring` will be informed if `monitored` stops.
-    pub fn monitor(&self, monitoring: &ActorId, monitored: &ActorId) {
-        let mut monitoring_by_monitored = self.data.monitoring_by_monitored.write().unwrap();
-        let monitoring_vec = monitoring_by_monitored
-            .entry(monitored.clone())
-            .or_insert(Vec::new());
-        monitoring_vec.push(monitoring.clone());
+    /// Registers the `aid` under a `name` that is unique across the cluster so that it can be
+    /// found with [`ActorSystem::find_global`] from any connected actor system. The registration
+    /// is replicated to every member and to actor systems that join later. If the name is
+    /// already registered to another actor an [`ActorError::NameAlreadyUsed`] is returned.
+    ///
+    /// The name is released when the actor stops or when the actor system the actor lives on
+    /// leaves the cluster. If two actors end up registered under the same name, for example
+    /// because the name was registered on both sides of a network partition, all members pick
+    /// the same winner once they are connected again and the losing actor is sent a
+    /// [`SystemMsg::GlobalNameLost`].
+    ///
+    /// # Examples
+    /// ```
+    /// use axiom::*;
+    ///
+    /// let system = ActorSystem::create(ActorSystemConfig::default());
+    /// system.init_current();
+    /// let aid = system.spawn(0, |_: &mut i32, _: ActorId, _: &Message| Status::Processed);
+    /// system.register_global("service", &aid).unwrap();
+    /// assert_eq!(Some(aid), system.find_global("service"));
+    /// system.trigger_shutdown();
+    /// ```
+    pub fn register_global(&self, name: &str, aid: &ActorId) -> Result<(), ActorError> {
+        {
+            let mut global_names = self.data.global_names.write().unwrap();
+            match global_names.get(name) {
+                Some(existing) if existing == aid => return Ok(()),
+                Some(_) => return Err(ActorError::NameAlreadyUsed(name.to_string())),
+                None => {
+                    global_names.insert(name.to_string(), aid.clone());
+                }
+            }
+        }
+        broadcast(
+            self,
+            &WireMessage::GlobalRegister {
+                name: name.to_string(),
+                aid: aid.clone(),
+            },
+        );
+        Ok(())
     }
-}
 
-impl fmt::Debug for ActorSystem {
-    fn fmt(&self, formatter: &'_ mut fmt::Formatter) -> fmt::Result {
-        write!(
-            formatter,
-            "ActorSystem{{uuid: {}, config: {:?}}}",
-            self.data.uuid.to_string(),
-            self.data.config,
-        )
+    /// Removes the global registration of the `name` across the cluster if there is one.
+    pub fn unregister_global(&self, name: &str) {
+        let removed = self.data.global_names.write().unwrap().remove(name);
+        if let Some(aid) = removed {
+            broadcast(
+                self,
+                &WireMessage::GlobalUnregister {
+                    name: name.to_string(),
+                    aid,
+                },
+            );
+        }
     }
-}
-
-// --------------------- Test Cases ---------------------
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::tests::*;
-    use std::time::Duration;
+    /// Looks up the [`ActorId`] registered under the global `name` and either returns the
+    /// located `aid` in a [`Option::Some`] or [`Option::None`] if not found.
+    pub fn find_global(&self, name: &str) -> Option<ActorId> {
+        let global_names = self.data.global_names.read().unwrap();
+        global_names.get(name).cloned()
+    }
 
-    /// A test helper to assert that a certain number of messages arrived in a certain time.
-    fn assert_await_received(aid: &ActorId, count: u8, timeout_ms: u64) {
-        use std::time::Instant;
-        let start = Instant::now();
-        let duration = Duration::from_millis(timeout_ms);
-        while aid.received() < count as usize {
-            if Instant::elapsed(&start) > duration {
-                assert!(
-                    false,
-                    "Timed out! count: {} timeout_ms: {}",
-                    count, timeout_ms
-                )
-            }
-        }
+    /// Returns a snapshot of the global names used to bring a newly joined member up to date.
+    pub(crate) fn global_names(&self) -> Vec<(String, ActorId)> {
+        let global_names = self.data.global_names.read().unwrap();
+        global_names
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect()
     }
 
-    /// A function that just returns [`Status::Processed`] which can be used as a handler for
-    /// a simple actor.
+    /// Merges a global name registered on a remote actor system. If the name is already held
+    /// by another actor the actor with the lowest actor system UUID and actor UUID keeps it so
+    /// that every member resolves the conflict the same way. If the loser is a local actor it
+    /// is sent a [`SystemMsg::GlobalNameLost`].
+    pub(crate) fn merge_global_name(&self, name: String, aid: ActorId) {
+        let loser = {
+            let mut global_names = self.data.global_names.write().unwrap();
+            match global_names.get(&name).cloned() {
+                None => {
+                    global_names.insert(name.clone(), aid);
+                    None
+                }
+                Some(existing) if existing == aid => None,
+                Some(existing) => {
+                    warn!(
+                        "Global name {} registered to both {:?} and {:?}",
+                        name, existing, aid
+                    );
+                    let key = |a: &ActorId| (a.system_uuid(), a.uuid());
+                    if key(&aid) < key(&existing) {
+                        global_names.insert(name.clone(), aid);
+                        Some(existing)
+                    } else {
+                        Some(aid)
+                    }
+                }
+            }
+        };
+        if let Some(loser) = loser {
+            if loser.is_local() {
+                let _ = loser.try_send(Message::new(SystemMsg::GlobalNameLost(name)));
+            }
+        }
+    }
+
+    /// Removes a global name unregistered on a remote actor system, but only if it is still
+    /// registered to the same `aid`.
+    pub(crate) fn remove_global_name(&self, name: &str, aid: &ActorId) {
+        let mut global_names = self.data.global_names.write().unwrap();
+        if global_names.get(name) == Some(aid) {
+            global_names.remove(name);
+        }
+    }
+
+    /// Links the two actors so that when either of them stops abnormally the other is stopped
+    /// as well, with a [`StopReason::LinkedExit`]. An actor that traps exits is sent a
+    /// [`SystemMsg::Exit`] instead, for any stop of the linked actor. If one of the actors has
+    /// already stopped the other is treated as if it stopped with a [`StopReason::Unknown`].
+    ///
+    /// Either actor can be on a remote actor system in which case losing the connection to that
+    /// actor system counts as the actor stopping with a [`StopReason::NodeDown`].
+    ///
+    /// # Examples
+    /// ```
+    /// use axiom::*;
+    ///
+    /// let system = ActorSystem::create(ActorSystemConfig::default());
+    /// system.init_current();
+    ///
+    /// let a = system.spawn(0, |_: &mut usize, _: ActorId, _: &Message| Status::Processed);
+    /// let b = system.spawn(0, |_: &mut usize, _: ActorId, _: &Message| Status::Processed);
+    /// system.link(&a, &b);
+    /// ```
+    pub fn link(&self, a: &ActorId, b: &ActorId) {
+        // The links of both local actors are recorded before any exit is delivered so that an
+        // exit for an actor that already stopped finds the link on the other side.
+        let mut stopped: Vec<(&ActorId, &ActorId)> = Vec::new();
+        {
+            let mut links = self.data.links.write().unwrap();
+            for (aid, linked) in [(a, b), (b, a)] {
+                if aid.is_local() {
+                    if self.is_alive(aid) {
+                        links.entry(aid.clone()).or_default().insert(linked.clone());
+                    } else {
+                        stopped.push((aid, linked));
+                    }
+                }
+            }
+        }
+        for (aid, linked) in stopped {
+            self.deliver_exit(linked, aid, &StopReason::Unknown);
+        }
+
+        for (aid, linked) in [(a, b), (b, a)] {
+            if !aid.is_local() {
+                let wire_message = WireMessage::Link {
+                    aid: aid.clone(),
+                    linked: linked.clone(),
+                };
+                if self
+                    .send_to_system(&aid.system_uuid(), &wire_message)
+                    .is_err()
+                {
+                    self.deliver_exit(linked, aid, &StopReason::NodeDown);
+                }
+            }
+        }
+    }
+
+    /// Records the link from the local actor with the given `aid` to the remote `linked` actor
+    /// as requested by the actor system of the `linked` actor. If the actor is no longer alive
+    /// the `linked` actor is sent the exit right away.
+    pub(crate) fn link_local(&self, aid: &ActorId, linked: &ActorId) {
+        {
+            let mut links = self.data.links.write().unwrap();
+            if self.is_alive(aid) {
+                links.entry(aid.clone()).or_default().insert(linked.clone());
+                return;
+            }
+        }
+        self.deliver_exit(linked, aid, &StopReason::Unknown);
+    }
+
+    /// Removes the link between the two actors.
+    pub fn unlink(&self, a: &ActorId, b: &ActorId) {
+        for (aid, linked) in [(a, b), (b, a)] {
+            if aid.is_local() {
+                self.unlink_local(aid, linked);
+            } else {
+                let wire_message = WireMessage::Unlink {
+                    aid: aid.clone(),
+                    linked: linked.clone(),
+                };
+                let _ = self.send_to_system(&aid.system_uuid(), &wire_message);
+            }
+        }
+    }
+
+    /// Removes the link from the local actor with the given `aid` to the `linked` actor and
+    /// returns whether the link existed.
+    pub(crate) fn unlink_local(&self, aid: &ActorId, linked: &ActorId) -> bool {
+        let mut links = self.data.links.write().unwrap();
+        match links.get_mut(aid) {
+            Some(linked_aids) => {
+                let removed = linked_aids.remove(linked);
+                if linked_aids.is_empty() {
+                    links.remove(aid);
+                }
+                removed
+            }
+            None => false,
+        }
+    }
+
+    /// Sets whether the local actor with the given `aid` traps exits. An actor trapping exits
+    /// is sent a [`SystemMsg::Exit`] when an actor linked to it stops rather than being stopped
+    /// itself.
+    pub fn trap_exit(&self, aid: &ActorId, trap: bool) {
+        let mut trapping_exits = self.data.trapping_exits.write().unwrap();
+        if trap {
+            trapping_exits.insert(aid.clone());
+        } else {
+            trapping_exits.remove(aid);
+        }
+    }
+
+    /// Sets the receive timeout of the local actor with the given `aid`. If the actor doesn't
+    /// process a message within the `timeout` it is sent a [`SystemMsg::ReceiveTimeout`]. The
+    /// timeout starts again whenever the actor processes a message, which means returning
+    /// [`Status::Processed`] or [`Status::ResetSkip`]; skipped messages don't count. Passing
+    /// `None` turns the receive timeout off. This is useful for actors that skip messages while
+    /// waiting for a reply that may never come.
+    ///
+    /// # Examples
+    /// ```
+    /// use axiom::*;
+    /// use std::time::Duration;
+    ///
+    /// let system = ActorSystem::create(ActorSystemConfig::default());
+    /// system.init_current();
+    ///
+    /// let aid = system.spawn(0, |_: &mut usize, _: ActorId, message: &Message| {
+    ///     if let Some(msg) = message.content_as::<SystemMsg>() {
+    ///         if let SystemMsg::ReceiveTimeout = &*msg {
+    ///             // Give up waiting.
+    ///             return Status::Stop;
+    ///         }
+    ///     }
+    ///     Status::Skipped
+    /// });
+    /// system.set_receive_timeout(&aid, Some(Duration::from_millis(100)));
+    /// ```
+    pub fn set_receive_timeout(&self, aid: &ActorId, timeout: Option<Duration>) {
+        let actor = self.data.actors_by_aid.read().unwrap().get(aid).cloned();
+        if let Some(actor) = actor {
+            Actor::set_receive_timeout(&actor, &self.data.timers, timeout);
+        }
+    }
+
+    /// Delivers the exit of the actor `from` that stopped for the `reason` to the actor with
+    /// the given `aid` it was linked to, wherever that actor lives.
+    fn deliver_exit(&self, aid: &ActorId, from: &ActorId, reason: &StopReason) {
+        if aid.is_local() {
+            self.linked_exit(aid, from, reason.clone());
+        } else {
+            let wire_message = WireMessage::Exit {
+                aid: aid.clone(),
+                from: from.clone(),
+                reason: reason.clone(),
+            };
+            if let Err(e) = self.send_to_system(&aid.system_uuid(), &wire_message) {
+                warn!("Unable to send exit to {:?}: {:?}", aid, e);
+            }
+        }
+    }
+
+    /// Handles the exit of the actor `from` that stopped for the `reason` for the local actor
+    /// with the given `aid` that was linked to it. If the link was removed in the meantime the
+    /// exit is ignored.
+    pub(crate) fn linked_exit(&self, aid: &ActorId, from: &ActorId, reason: StopReason) {
+        if !self.unlink_local(aid, from) {
+            return;
+        }
+        let trapping = self.data.trapping_exits.read().unwrap().contains(aid);
+        if trapping {
+            // Local actors are shutting down as well so there is no one to tell.
+            if reason != StopReason::SystemShutdown {
+                let message = Message::new(SystemMsg::Exit {
+                    aid: from.clone(),
+                    reason,
+                });
+                if let Err(e) = aid.try_send(message) {
+                    warn!("Unable to send exit to {:?}: {:?}", aid, e);
+                }
+            }
+        } else if reason.is_abnormal() {
+            self.stop_actor(aid.clone(), StopReason::LinkedExit(from.clone()));
+        }
+    }
+
+    /// Adds a monitor so that `monitoring` will be sent a [`SystemMsg::Stopped`] with the
+    /// reason when `monitored` stops. If `monitored` has already stopped the message is sent
+    /// right away with a [`StopReason::Unknown`].
+    ///
+    /// Either of the actors can be on a remote actor system. When `monitored` is remote, the
+    /// actor system it lives on is asked to inform `monitoring` when it stops, and if the
+    /// connection to that actor system is lost `monitoring` is sent a [`StopReason::NodeDown`]
+    /// as the actor can no longer be reached. If the actor system of `monitored` is not
+    /// connected at all, the [`StopReason::NodeDown`] is sent right away.
+    pub fn monitor(&self, monitoring: &ActorId, monitored: &ActorId) {
+        if monitored.is_local() {
+            self.monitor_local(monitoring.clone(), monitored.clone());
+            return;
+        }
+
+        if monitoring.is_local() {
+            let mut monitoring_by_monitored = self.data.monitoring_by_monitored.write().unwrap();
+            let monitoring_vec = monitoring_by_monitored
+                .entry(monitored.clone())
+                .or_default();
+            monitoring_vec.push(monitoring.clone());
+        }
+        let wire_message = WireMessage::Monitor {
+            monitoring: monitoring.clone(),
+            monitored: monitored.clone(),
+        };
+        if self
+            .send_to_system(&monitored.system_uuid(), &wire_message)
+            .is_err()
+        {
+            self.remote_stopped(monitored, StopReason::NodeDown);
+        }
+    }
+
+    /// Adds a monitor for the local `monitored` actor, which is also how monitors requested by
+    /// remote actor systems are added. If the `monitored` actor is no longer alive the
+    /// `monitoring` actor is told right away that the actor has stopped. The check is done
+    /// while holding the monitors lock so that it can't race with [`ActorSystem::stop`].
+    pub(crate) fn monitor_local(&self, monitoring: ActorId, monitored: ActorId) {
+        {
+            let mut monitoring_by_monitored = self.data.monitoring_by_monitored.write().unwrap();
+            if self.is_alive(&monitored) {
+                let monitoring_vec = monitoring_by_monitored.entry(monitored).or_default();
+                monitoring_vec.push(monitoring);
+                return;
+            }
+        }
+        let result = if monitoring.is_local() {
+            monitoring.try_send(Message::new(SystemMsg::Stopped {
+                aid: monitored,
+                reason: StopReason::Unknown,
+            }))
+        } else {
+            let wire_message = WireMessage::Stopped {
+                aid: monitored,
+                reason: StopReason::Unknown,
+            };
+            self.send_to_system(&monitoring.system_uuid(), &wire_message)
+        };
+        if let Err(e) = result {
+            warn!("Unable to send stopped to {:?}: {:?}", monitoring, e);
+        }
+    }
+
+    /// Informs the local actors monitoring the remote actor with the given `aid` that it has
+    /// stopped for the given `reason` and removes the actor from the map of monitors.
+    pub(crate) fn remote_stopped(&self, aid: &ActorId, reason: StopReason) {
+        let monitoring = self
+            .data
+            .monitoring_by_monitored
+            .write()
+            .unwrap()
+            .remove(aid);
+        for m_aid in monitoring.unwrap_or_default() {
+            let message = Message::new(SystemMsg::Stopped {
+                aid: aid.clone(),
+                reason: reason.clone(),
+            });
+            if let Err(e) = m_aid.try_send(message) {
+                warn!("Unable to send stopped to {:?}: {:?}", m_aid, e);
+            }
+        }
+    }
+
+    /// Starts listening for connections from other actor systems on the given `address` and
+    /// returns the address that was actually bound. Passing a port of `0` will bind to any
+    /// available port which is useful for tests. Connections are accepted on a background
+    /// thread until the actor system is shut down.
+    ///
+    /// # Examples
+    /// ```
+    /// use axiom::*;
+    ///
+    /// let system = ActorSystem::create(ActorSystemConfig::default());
+    /// let address = system.listen("127.0.0.1:0").unwrap();
+    /// assert!(address.port() > 0);
+    /// system.trigger_shutdown();
+    /// ```
+    pub fn listen<A: ToSocketAddrs>(&self, address: A) -> io::Result<SocketAddr> {
+        let listener = TcpListener::bind(address)?;
+        let local_address = listener.local_addr()?;
+        start_listener(self.clone(), listener, self.data.config.thread_wait_time)?;
+        *self.data.listen_address.write().unwrap() = Some(local_address);
+        info!(
+            "Actor system {} listening on {}",
+            self.data.uuid, local_address
+        );
+        Ok(local_address)
+    }
+
+    /// Connects this actor system to the actor system listening on the given `address` and
+    /// returns the UUID of the remote actor system once the connection is established. After
+    /// connecting, [`ActorId`]s of actors on either system can be sent messages from the other.
+    pub fn connect<A: ToSocketAddrs>(&self, address: A) -> io::Result<Uuid> {
+        let stream = TcpStream::connect(address)?;
+        handshake(self.clone(), stream)
+    }
+
+    /// Returns the address the actor system is listening on for connections from other actor
+    /// systems or `None` if the actor system is not listening.
+    pub fn listen_address(&self) -> Option<SocketAddr> {
+        *self.data.listen_address.read().unwrap()
+    }
+
+    /// Checks to see if this actor system currently has a connection to the actor system with
+    /// the given UUID.
+    pub fn is_connected(&self, system_uuid: &Uuid) -> bool {
+        let connections = self.data.connections.read().unwrap();
+        connections.contains_key(system_uuid)
+    }
+
+    /// Returns the UUIDs of all of the actor systems that this actor system is connected to,
+    /// which is the membership of the cluster from the point of view of this actor system.
+    pub fn members(&self) -> Vec<Uuid> {
+        let connections = self.data.connections.read().unwrap();
+        connections.keys().cloned().collect()
+    }
+
+    /// Adds a monitor so that `monitoring` will be sent a [`SystemMsg::NodeJoined`] when an
+    /// actor system joins the cluster and a [`SystemMsg::NodeLeft`] when one leaves. The
+    /// monitoring actor is immediately sent a [`SystemMsg::NodeJoined`] for every actor system
+    /// that is already connected so that it doesn't miss any members.
+    pub fn monitor_nodes(&self, monitoring: &ActorId) {
+        let members = {
+            let mut node_monitors = self.data.node_monitors.write().unwrap();
+            node_monitors.push(monitoring.clone());
+            self.members()
+        };
+        for system_uuid in members {
+            let message = Message::new(SystemMsg::NodeJoined(system_uuid));
+            if let Err(e) = monitoring.try_send(message) {
+                warn!(
+                    "Unable to inform {:?} of {}: {:?}",
+                    monitoring, system_uuid, e
+                );
+            }
+        }
+    }
+
+    /// Sends the `message` to every actor monitoring the cluster membership. Actors that have
+    /// stopped are removed from the monitors. The messages are sent without holding the lock on
+    /// the monitors as a send to a full channel waits for room in it.
+    pub(crate) fn notify_node_monitors(&self, message: SystemMsg) {
+        let message = Message::new(message);
+        let node_monitors = self.data.node_monitors.read().unwrap().clone();
+        let mut stopped = Vec::new();
+        for aid in node_monitors {
+            match aid.try_send(message.clone()) {
+                Err(ActorError::ActorStopped) => stopped.push(aid),
+                Err(e) => warn!("Unable to inform {:?} of a cluster change: {:?}", aid, e),
+                Ok(()) => (),
+            }
+        }
+        if !stopped.is_empty() {
+            let mut node_monitors = self.data.node_monitors.write().unwrap();
+            node_monitors.retain(|aid| !stopped.contains(aid));
+        }
+    }
+
+    /// Returns a snapshot of the current connections to remote actor systems.
+    pub(crate) fn connections(&self) -> Vec<Arc<Connection>> {
+        let connections = self.data.connections.read().unwrap();
+        connections.values().cloned().collect()
+    }
+
+    /// Closes the connection to the actor system with the given UUID if there is one.
+    pub fn disconnect(&self, system_uuid: &Uuid) {
+        let connection = {
+            let connections = self.data.connections.read().unwrap();
+            connections.get(system_uuid).cloned()
+        };
+        // The reader thread of the connection will remove it and inform the node monitors.
+        if let Some(connection) = connection {
+            connection.close();
+        }
+    }
+
+    /// Registers a newly established connection to a remote actor system. If two actor systems
+    /// connect to each other at the same time there will be two connections between them. In
+    /// that case both sides keep only the connection with the lower connection id and close the
+    /// other, so that they end up agreeing on the connection to use.
+    pub(crate) fn register_connection(&self, connection: Arc<Connection>) -> Registration {
+        let mut connections = self.data.connections.write().unwrap();
+        let system_uuid = connection.system_uuid();
+        match connections.get(&system_uuid).cloned() {
+            None => {
+                connections.insert(system_uuid, connection);
+                Registration::Joined
+            }
+            Some(existing) => {
+                if connection.connection_id() < existing.connection_id() {
+                    connections.insert(system_uuid, connection);
+                    existing.close();
+                    Registration::Replaced
+                } else {
+                    Registration::Rejected
+                }
+            }
+        }
+    }
+
+    /// Cleans up after the actor system with the given UUID has left the cluster. Every local
+    /// actor monitoring an actor on that actor system is sent a [`StopReason::NodeDown`] since
+    /// the actor can no longer be reached, and monitors held by actors on that actor system are
+    /// removed, as are the global names held by actors on that actor system. Local actors
+    /// linked to actors on that actor system get an exit with a [`StopReason::NodeDown`].
+    /// Finally the node monitors are sent a [`SystemMsg::NodeLeft`].
+    pub(crate) fn node_left(&self, system_uuid: &Uuid) {
+        self.data
+            .global_names
+            .write()
+            .unwrap()
+            .retain(|_, aid| aid.system_uuid() != *system_uuid);
+        let mut exits: Vec<(ActorId, ActorId)> = Vec::new();
+        {
+            let links = self.data.links.read().unwrap();
+            for (aid, linked_aids) in links.iter() {
+                for l_aid in linked_aids {
+                    if l_aid.system_uuid() == *system_uuid {
+                        exits.push((aid.clone(), l_aid.clone()));
+                    }
+                }
+            }
+        }
+        for (aid, from) in exits {
+            self.linked_exit(&aid, &from, StopReason::NodeDown);
+        }
+
+        let mut unreachable: Vec<(ActorId, Vec<ActorId>)> = Vec::new();
+        {
+            let mut monitoring_by_monitored = self.data.monitoring_by_monitored.write().unwrap();
+            monitoring_by_monitored.retain(|monitored, monitoring| {
+                if monitored.system_uuid() == *system_uuid {
+                    unreachable.push((monitored.clone(), std::mem::take(monitoring)));
+                    false
+                } else {
+                    monitoring.retain(|m_aid| m_aid.system_uuid() != *system_uuid);
+                    !monitoring.is_empty()
+                }
+            });
+        }
+        for (aid, monitoring) in unreachable {
+            for m_aid in monitoring {
+                let _ = m_aid.try_send(Message::new(SystemMsg::Stopped {
+                    aid: aid.clone(),
+                    reason: StopReason::NodeDown,
+                }));
+            }
+        }
+        self.notify_node_monitors(SystemMsg::NodeLeft(*system_uuid));
+    }
+
+    /// Removes the given connection from the connections table but only if it is still the
+    /// registered connection for its actor system. This prevents a reader thread of a replaced
+    /// connection from removing its replacement. Returns `true` if the connection was removed
+    /// and thus the remote actor system has left the cluster.
+    pub(crate) fn connection_closed(&self, connection: &Arc<Connection>) -> bool {
+        let mut connections = self.data.connections.write().unwrap();
+        let registered = connections.get(&connection.system_uuid()).cloned();
+        match registered {
+            Some(current) if Arc::ptr_eq(&current, connection) => {
+                connections.remove(&connection.system_uuid());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Sends the `message` to the remote actor with the given `aid` over the connection to the
+    /// actor system the actor lives on.
+    fn send_to_remote(
+        &self,
+        aid: &ActorId,
+        message: Message,
+        priority: u8,
+    ) -> Result<(), ActorError> {
+        let wire_message = WireMessage::Deliver {
+            actor_uuid: aid.uuid(),
+            message,
+            priority,
+        };
+        self.send_to_system(&aid.system_uuid(), &wire_message)
+    }
+
+    /// Sends the `wire_message` over the connection to the actor system with the given UUID.
+    pub(crate) fn send_to_system(
+        &self,
+        system_uuid: &Uuid,
+        wire_message: &WireMessage,
+    ) -> Result<(), ActorError> {
+        let connection = {
+            let connections = self.data.connections.read().unwrap();
+            connections.get(system_uuid).cloned()
+        };
+        match connection {
+            None => Err(ActorError::NotConnected(*system_uuid)),
+            Some(connection) => match connection.send(wire_message) {
+                Ok(_) => Ok(()),
+                // The message couldn't be encoded so nothing was written to the connection.
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                    Err(ActorError::Serialization(e.to_string()))
+                }
+                Err(e) => {
+                    // A failed write means the connection is no longer usable.
+                    error!("Error sending to {}: {:?}", system_uuid, e);
+                    connection.close();
+                    Err(ActorError::NotConnected(*system_uuid))
+                }
+            },
+        }
+    }
+
+    /// Registers a new request sent with [`ActorId::ask`] that times out after `timeout` and
+    /// returns the handle for it.
+    fn register_ask(&self, timeout: Duration) -> AskHandle {
+        let request_id = Uuid::new_v4();
+        let state = Arc::new(AskState {
+            result: Mutex::new(None),
+            condvar: Condvar::new(),
+            waker: Mutex::new(None),
+        });
+        let mut pending_asks = self.data.pending_asks.lock().unwrap();
+        pending_asks.insert(request_id, state.clone());
+        AskHandle {
+            system: self.clone(),
+            request_id,
+            deadline: Instant::now() + timeout,
+            state,
+            timer: None,
+        }
+    }
+
+    /// Completes the pending request with the given id with the `result`. Requests that have
+    /// already been completed are ignored.
+    pub(crate) fn complete_ask(&self, request_id: &Uuid, result: Result<Message, ActorError>) {
+        let state = self.data.pending_asks.lock().unwrap().remove(request_id);
+        match state {
+            Some(state) => state.complete(result),
+            None if result.is_ok() => {
+                debug!("Discarding reply to finished request {}", request_id)
+            }
+            None => (),
+        }
+    }
+
+    /// Sends the `reply` to the actor system waiting for it as given by `reply_to`.
+    pub(crate) fn send_reply(&self, reply_to: ReplyTo, reply: Message) -> Result<(), ActorError> {
+        if reply_to.system_uuid == self.data.uuid {
+            self.complete_ask(&reply_to.request_id, Ok(reply));
+            Ok(())
+        } else {
+            let wire_message = WireMessage::Reply {
+                request_id: reply_to.request_id,
+                message: reply,
+            };
+            self.send_to_system(&reply_to.system_uuid, &wire_message)
+        }
+    }
+
+    /// Delivers a `message` that arrived from a remote actor system to the local actor with
+    /// the given UUID with the `priority` it was sent with. If the actor no longer exists the
+    /// message is discarded.
+    pub(crate) fn deliver_from_remote(&self, actor_uuid: &Uuid, message: Message, priority: u8) {
+        match self.find_aid_by_uuid(actor_uuid) {
+            Some(aid) => {
+                if let Err(e) = aid.try_send_with_priority(message, priority) {
+                    warn!("Unable to deliver remote message to {:?}: {:?}", aid, e);
+                }
+            }
+            None => warn!(
+                "Received remote message for actor {} which is not on system {}",
+                actor_uuid, self.data.uuid
+            ),
+        }
+    }
+}
+
+impl fmt::Debug for ActorSystem {
+    fn fmt(&self, formatter: &'_ mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "ActorSystem{{uuid: {}, config: {:?}}}",
+            self.data.uuid, self.data.config,
+        )
+    }
+}
+
+// --------------------- Test Cases ---------------------
+
+#[cfg(test)]
+#[allow(
+    clippy::assertions_on_constants,
+    clippy::bool_assert_comparison,
+    clippy::explicit_auto_deref,
+    clippy::unnecessary_cast,
+    non_fmt_panics
+)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+    use std::rc::Rc;
+
+    /// A function that just returns [`Status::Processed`] which can be used as a handler for
+    /// a simple actor.
     fn simple_handler(_state: &mut usize, _aid: ActorId, _message: &Message) -> Status {
         Status::Processed
     }
@@ -1317,8 +3422,10 @@ mod tests {
             },
         );
 
-        // Send a message to the actor.
+        // Send a message to the actor. The stop message would be received ahead of it so we
+        // wait for the message to be processed first.
         aid.send(Message::new(11 as i32));
+        assert_await_received(&aid, 2, 1000);
         aid.send(Message::new(SystemMsg::Stop));
 
         // Wait for the message to get there because test is asynchronous.
@@ -1496,7 +3603,7 @@ mod tests {
     fn monitor_handler(state: &mut ActorId, _aid: ActorId, message: &Message) -> Status {
         if let Some(msg) = message.content_as::<SystemMsg>() {
             match &*msg {
-                SystemMsg::Stopped(aid) => {
+                SystemMsg::Stopped { aid, .. } => {
                     assert!(Arc::ptr_eq(&state.data, &aid.data));
                     Status::Processed
                 }
@@ -1542,4 +3649,832 @@ mod tests {
         // Wait for the message to get there because test is asynchronous.
         system.trigger_and_await_shutdown();
     }
+
+    #[test]
+    fn test_stop_reasons() {
+        init_test_log();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+        let (monitoring, reasons) = spawn_reason_recorder(&system);
+
+        // An actor returning a stop status stops normally.
+        let normal = system.spawn(0_usize, |_: &mut usize, _: ActorId, message: &Message| {
+            if message.content_as::<i32>().is_some() {
+                Status::Stop
+            } else {
+                Status::Processed
+            }
+        });
+        system.monitor(&monitoring, &normal);
+        normal.send(Message::new(11));
+        assert_await_received(&monitoring, 2, 1000);
+
+        // Stopping an actor through the actor system is a forced stop.
+        let forced = system.spawn(0_usize, simple_handler);
+        system.monitor(&monitoring, &forced);
+        system.stop(forced.clone());
+        assert_await_received(&monitoring, 3, 1000);
+
+        // Monitoring an actor that is already gone is answered right away.
+        system.monitor(&monitoring, &forced);
+        assert_await_received(&monitoring, 4, 1000);
+
+        assert_eq!(
+            vec![StopReason::Normal, StopReason::Forced, StopReason::Unknown],
+            *reasons.lock().unwrap()
+        );
+        assert!(!StopReason::Normal.is_abnormal());
+        assert!(!StopReason::SystemShutdown.is_abnormal());
+        assert!(StopReason::Forced.is_abnormal());
+        assert!(StopReason::Panic("Boom".to_string()).is_abnormal());
+
+        system.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_panic_in_processor() {
+        init_test_log();
+
+        // A single dispatcher thread makes sure that the thread survives the panic.
+        let system = ActorSystem::create(ActorSystemConfig {
+            thread_pool_size: 1,
+            ..ActorSystemConfig::default()
+        });
+        system.init_current();
+        let panicking = system.spawn(0_usize, |_: &mut usize, _: ActorId, message: &Message| {
+            if let Some(msg) = message.content_as::<i32>() {
+                panic!("Boom {}", msg);
+            }
+            Status::Processed
+        });
+
+        let (monitoring, reasons) = spawn_reason_recorder(&system);
+        system.monitor(&monitoring, &panicking);
+
+        panicking.send(Message::new(11));
+        assert_await_received(&monitoring, 2, 1000);
+        assert_eq!(
+            vec![StopReason::Panic("Boom 11".to_string())],
+            *reasons.lock().unwrap()
+        );
+        assert!(!system.is_alive(&panicking));
+        assert_eq!(
+            Err(ActorError::ActorStopped),
+            panicking.try_send(Message::new(11))
+        );
+
+        // The dispatcher thread is still around to process messages for other actors.
+        let aid = system.spawn(0_usize, simple_handler);
+        aid.send(Message::new(11));
+        assert_await_received(&aid, 2, 1000);
+
+        system.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_links() {
+        init_test_log();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+        let (monitoring, reasons) = spawn_reason_recorder(&system);
+
+        // A chain of linked actors fails together when the first one panics.
+        let a = spawn_crashing(&system);
+        let b = spawn_crashing(&system);
+        let c = spawn_crashing(&system);
+        system.link(&a, &b);
+        system.link(&b, &c);
+        system.monitor(&monitoring, &b);
+        system.monitor(&monitoring, &c);
+        a.send(Message::new(11));
+        assert_await_received(&monitoring, 3, 1000);
+        assert!(!system.is_alive(&b));
+        assert!(!system.is_alive(&c));
+        assert_eq!(
+            vec![
+                StopReason::LinkedExit(a.clone()),
+                StopReason::LinkedExit(b.clone())
+            ],
+            *reasons.lock().unwrap()
+        );
+
+        // A normal stop doesn't take down the linked actor.
+        let d = spawn_crashing(&system);
+        let e = spawn_crashing(&system);
+        system.link(&d, &e);
+        d.send(Message::new(true));
+        assert_await(|| !system.is_alive(&d), 1000);
+        assert!(system.is_alive(&e));
+
+        // Unlinked actors are no longer affected.
+        let f = spawn_crashing(&system);
+        system.link(&e, &f);
+        system.unlink(&f, &e);
+        e.send(Message::new(11));
+        assert_await(|| !system.is_alive(&e), 1000);
+        assert!(system.is_alive(&f));
+
+        // Linking to an actor that has already stopped stops the other one.
+        system.link(&f, &e);
+        assert!(!system.is_alive(&f));
+
+        system.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_trap_exit() {
+        init_test_log();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+
+        // An actor trapping exits is told about any stop of a linked actor and keeps running.
+        let (trapping, reasons) = spawn_reason_recorder(&system);
+        system.trap_exit(&trapping, true);
+        let a = spawn_crashing(&system);
+        let b = spawn_crashing(&system);
+        system.link(&trapping, &a);
+        system.link(&trapping, &b);
+        a.send(Message::new(11));
+        b.send(Message::new(true));
+        assert_await_received(&trapping, 3, 1000);
+        assert!(system.is_alive(&trapping));
+        let mut received = reasons.lock().unwrap().clone();
+        received.sort_by_key(|r| format!("{:?}", r));
+        assert_eq!(
+            vec![
+                StopReason::Normal,
+                StopReason::Panic("Crashing as requested.".to_string())
+            ],
+            received
+        );
+
+        // Once it stops trapping exits it is stopped along with the linked actor.
+        let c = spawn_crashing(&system);
+        system.link(&trapping, &c);
+        system.trap_exit(&trapping, false);
+        c.send(Message::new(11));
+        assert_await(|| !system.is_alive(&trapping), 1000);
+
+        system.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_receive_timeout() {
+        init_test_log();
+        let clock = Arc::new(VirtualClock::new());
+        let system = ActorSystem::create_with_clock(ActorSystemConfig::default(), clock.clone());
+        system.init_current();
+
+        // The actor processes an i32, skips a bool and counts the receive timeouts.
+        let timeouts = Arc::new(Mutex::new(0_usize));
+        let aid = system.spawn(
+            timeouts.clone(),
+            |state: &mut Arc<Mutex<usize>>, _: ActorId, message: &Message| {
+                if message.content_as::<bool>().is_some() {
+                    Status::Skipped
+                } else {
+                    if let Some(msg) = message.content_as::<SystemMsg>() {
+                        if let SystemMsg::ReceiveTimeout = &*msg {
+                            *state.lock().unwrap() += 1;
+                        }
+                    }
+                    Status::Processed
+                }
+            },
+        );
+        assert_await_received(&aid, 1, 1000);
+        system.set_receive_timeout(&aid, Some(Duration::from_secs(10)));
+
+        // Processing a message restarts the timeout.
+        clock.advance(Duration::from_secs(5));
+        aid.send(Message::new(11));
+        assert_await_received(&aid, 2, 1000);
+        clock.advance(Duration::from_secs(5));
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(0, *timeouts.lock().unwrap());
+
+        // Skipping a message doesn't.
+        aid.send(Message::new(true));
+        thread::sleep(Duration::from_millis(50));
+        clock.advance(Duration::from_secs(5));
+        assert_await(|| *timeouts.lock().unwrap() == 1, 1000);
+
+        // A timeout that is processed after the actor processed another message is dropped,
+        // as is one that the timer didn't send.
+        {
+            let actor = system.data.actors_by_aid.read().unwrap()[&aid].clone();
+            let timers = system.timers();
+            let current = actor
+                .receive_timeout
+                .lock()
+                .unwrap()
+                .as_ref()
+                .unwrap()
+                .message
+                .clone();
+            assert!(Actor::is_stale_timeout(&actor, timers, &current));
+            let old = Message::new(SystemMsg::ReceiveTimeout);
+            assert!(Actor::is_stale_timeout(&actor, timers, &old));
+            clock.advance(Duration::from_secs(10));
+            assert!(!Actor::is_stale_timeout(&actor, timers, &current));
+            assert!(Actor::is_stale_timeout(&actor, timers, &old));
+            assert_await(|| *timeouts.lock().unwrap() == 2, 1000);
+        }
+
+        // Once the timeout is turned off no more timeouts are sent.
+        system.set_receive_timeout(&aid, None);
+        clock.advance(Duration::from_secs(20));
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(2, *timeouts.lock().unwrap());
+
+        system.trigger_and_await_shutdown();
+    }
+
+    /// The state of an actor that blocks on its start message until the gate is opened and
+    /// then records the `i32` messages it receives.
+    type Gated = (Arc<(Mutex<bool>, Condvar)>, Arc<Mutex<Vec<i32>>>);
+
+    /// Spawns a gated actor with a channel of `channel_size` messages that uses the `overflow`
+    /// policy. The actor blocks on the start message, which is in the system lane so it takes
+    /// none of the slots.
+    fn spawn_gated(
+        system: &ActorSystem,
+        channel_size: u16,
+        overflow: OverflowPolicy,
+    ) -> (ActorId, Gated) {
+        let config = ActorConfig {
+            channel_size,
+            overflow,
+            ..ActorConfig::default()
+        };
+        spawn_gated_with_config(system, config)
+    }
+
+    /// Spawns a gated actor with the given `config`.
+    fn spawn_gated_with_config(system: &ActorSystem, config: ActorConfig) -> (ActorId, Gated) {
+        let gated: Gated = (
+            Arc::new((Mutex::new(false), Condvar::new())),
+            Arc::new(Mutex::new(Vec::new())),
+        );
+        let aid = system.spawn_with_config(
+            config,
+            gated.clone(),
+            |state: &mut Gated, _: ActorId, message: &Message| {
+                if let Some(value) = message.content_as::<i32>() {
+                    state.1.lock().unwrap().push(*value);
+                } else {
+                    let (mutex, condvar) = &*state.0;
+                    let mut open = mutex.lock().unwrap();
+                    while !*open {
+                        open = condvar.wait(open).unwrap();
+                    }
+                }
+                Status::Processed
+            },
+        );
+        (aid, gated)
+    }
+
+    /// Opens the gate of a gated actor and waits until it has recorded `count` messages.
+    fn open_gate(gated: &Gated, count: usize) -> Vec<i32> {
+        let (mutex, condvar) = &*gated.0;
+        *mutex.lock().unwrap() = true;
+        condvar.notify_all();
+        assert_await(|| gated.1.lock().unwrap().len() == count, 1000);
+        thread::sleep(Duration::from_millis(20));
+        gated.1.lock().unwrap().clone()
+    }
+
+    #[test]
+    fn test_channel_overflow() {
+        init_test_log();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+
+        // A full channel fails the send.
+        let (aid, gated) = spawn_gated(&system, 2, OverflowPolicy::Fail);
+        assert_eq!(Ok(()), aid.try_send(Message::new(1)));
+        assert_eq!(Ok(()), aid.try_send(Message::new(2)));
+        assert_eq!(Err(ActorError::ChannelFull), aid.try_send(Message::new(3)));
+        assert_eq!(vec![1, 2], open_gate(&gated, 2));
+
+        // The message sent to a full channel is dropped.
+        let (aid, gated) = spawn_gated(&system, 2, OverflowPolicy::DropNewest);
+        for i in 1..4 {
+            assert_eq!(Ok(()), aid.try_send(Message::new(i)));
+        }
+        assert_eq!(vec![1, 2], open_gate(&gated, 2));
+
+        // The oldest message is dropped to make room.
+        let (aid, gated) = spawn_gated(&system, 2, OverflowPolicy::DropOldest);
+        for i in 1..4 {
+            assert_eq!(Ok(()), aid.try_send(Message::new(i)));
+        }
+        assert_eq!(vec![2, 3], open_gate(&gated, 2));
+        assert!(system.is_alive(&aid));
+
+        system.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_priorities() {
+        init_test_log();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+
+        // Higher priorities are received first and the same priority in the order sent. A
+        // priority above the highest level counts as the highest level.
+        let config = ActorConfig {
+            priority_levels: 3,
+            ..ActorConfig::default()
+        };
+        let (aid, gated) = spawn_gated_with_config(&system, config);
+        for &(value, priority) in &[(1, 0), (2, 1), (3, 2), (4, 0), (5, 200), (6, 1)] {
+            aid.send_with_priority(Message::new(value), priority);
+        }
+        assert_eq!(vec![3, 5, 2, 6, 1, 4], open_gate(&gated, 6));
+
+        // A stop message doesn't wait for the backlog of user messages.
+        let (aid, gated) = spawn_gated(&system, 32, OverflowPolicy::Block);
+        for i in 0..10 {
+            aid.send(Message::new(i));
+        }
+        aid.send(Message::new(SystemMsg::Stop));
+        open_gate(&gated, 0);
+        assert_await(|| aid.is_stopped(), 1000);
+        assert!(gated.1.lock().unwrap().is_empty());
+
+        system.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_actor_builder() {
+        init_test_log();
+        let clock = Arc::new(VirtualClock::new());
+        let system = ActorSystem::create_with_clock(ActorSystemConfig::default(), clock.clone());
+        system.init_current();
+
+        let (parent, reasons) = spawn_reason_recorder(&system);
+        system.trap_exit(&parent, true);
+        let (monitoring, stopped) = spawn_reason_recorder(&system);
+        let aid = system
+            .actor_builder()
+            .name("built")
+            .channel_size(4)
+            .overflow(OverflowPolicy::Fail)
+            .parent(&parent)
+            .monitored_by(&monitoring)
+            .receive_timeout(Duration::from_secs(1))
+            .spawn(
+                0_usize,
+                |_: &mut usize, _: ActorId, message: &Message| match message
+                    .content_as::<SystemMsg>()
+                {
+                    Some(m) if *m == SystemMsg::ReceiveTimeout => Status::Stop,
+                    _ => Status::Processed,
+                },
+            )
+            .unwrap();
+        assert_eq!(Some(aid.clone()), system.find_aid_by_name("built"));
+
+        // The name is taken now.
+        let result = system
+            .actor_builder()
+            .name("built")
+            .spawn(0_usize, simple_handler);
+        assert_eq!(
+            Err(ActorError::NameAlreadyUsed("built".to_string())),
+            result
+        );
+
+        // The receive timeout stops the actor which the parent and the monitor are told about.
+        assert_await_received(&aid, 1, 1000);
+        clock.advance(Duration::from_secs(1));
+        assert_await(|| !system.is_alive(&aid), 1000);
+        assert_await(|| reasons.lock().unwrap().len() == 1, 1000);
+        assert_await(|| stopped.lock().unwrap().len() == 1, 1000);
+        assert_eq!(vec![StopReason::Normal], *reasons.lock().unwrap());
+        assert_eq!(vec![StopReason::Normal], *stopped.lock().unwrap());
+
+        system.trigger_and_await_shutdown();
+    }
+
+    /// Counts the turns a gated actor takes through the work channel to process its start
+    /// message and 20 more messages when batches are limited to `max_batch_messages`.
+    fn count_turns(scheduler: SchedulerKind, max_batch_messages: u16) -> usize {
+        let system = ActorSystem::create(ActorSystemConfig {
+            scheduler,
+            thread_pool_size: 1,
+            max_batch_messages,
+            max_batch_micros: u32::MAX,
+            ..ActorSystemConfig::default()
+        });
+        system.init_current();
+
+        // All of the messages are waiting when the gate is opened so the number of turns
+        // depends only on the size of the batches.
+        let (aid, gated) = spawn_gated(&system, 32, OverflowPolicy::Block);
+        for i in 0..20 {
+            aid.send(Message::new(i));
+        }
+        assert_eq!((0..20).collect::<Vec<i32>>(), open_gate(&gated, 20));
+        let turns = system.received();
+        system.trigger_and_await_shutdown();
+        turns
+    }
+
+    #[test]
+    fn test_batching() {
+        init_test_log();
+        // Each count runs on its own thread as a thread can only have one current system.
+        for &scheduler in &[SchedulerKind::GlobalQueue, SchedulerKind::WorkStealing] {
+            assert_eq!(
+                21,
+                thread::spawn(move || count_turns(scheduler, 1))
+                    .join()
+                    .unwrap()
+            );
+            assert_eq!(
+                3,
+                thread::spawn(move || count_turns(scheduler, 10))
+                    .join()
+                    .unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_parked_dispatchers() {
+        init_test_log();
+        // A long wait time shows that neither waking up for work nor shutting down depend on it.
+        let system = ActorSystem::create(ActorSystemConfig {
+            thread_wait_time: 5000,
+            ..ActorSystemConfig::default()
+        });
+        system.init_current();
+        let aid = system.spawn(0_usize, simple_handler);
+        assert_await_received(&aid, 1, 1000);
+
+        // Give the dispatcher threads time to park.
+        thread::sleep(Duration::from_millis(50));
+        let start = Instant::now();
+        aid.send(Message::new(11));
+        assert_await_received(&aid, 2, 1000);
+        assert!(start.elapsed() < Duration::from_millis(500));
+
+        thread::sleep(Duration::from_millis(50));
+        let start = Instant::now();
+        system.trigger_and_await_shutdown();
+        assert!(start.elapsed() < Duration::from_millis(500));
+    }
+
+    /// Sends messages to an actor from several threads at once on a system using the
+    /// `scheduler` and checks that the actor is never in the work queue more than once and that
+    /// it processes every message.
+    fn assert_scheduled_once(scheduler: SchedulerKind) {
+        let system = ActorSystem::create(ActorSystemConfig {
+            scheduler,
+            max_batch_messages: 3,
+            ..ActorSystemConfig::default()
+        });
+        system.init_current();
+        let aid = system.spawn(0_usize, simple_handler);
+
+        let senders: Vec<_> = (0..4)
+            .map(|_| {
+                let system = system.clone();
+                let aid = aid.clone();
+                thread::spawn(move || {
+                    system.init_current();
+                    for i in 0..1000 {
+                        aid.send(Message::new(i));
+                    }
+                })
+            })
+            .collect();
+        while senders.iter().any(|s| !s.is_finished()) {
+            assert!(system.pending() <= 1);
+        }
+        for sender in senders {
+            sender.join().unwrap();
+        }
+
+        // Nothing is stranded and the actor was never taken without a message to process.
+        assert_await(|| aid.received() == 4001, 5000);
+        assert_await(
+            || aid.schedule_state().0.load(Ordering::SeqCst) == ScheduleState::IDLE,
+            1000,
+        );
+        assert_eq!(0, system.pending());
+        assert_eq!(system.sent(), system.received());
+
+        system.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_schedule_state() {
+        init_test_log();
+        for &scheduler in &[SchedulerKind::GlobalQueue, SchedulerKind::WorkStealing] {
+            thread::spawn(move || assert_scheduled_once(scheduler))
+                .join()
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_dispatchers() {
+        init_test_log();
+        let system = ActorSystem::create(ActorSystemConfig {
+            thread_pool_size: 1,
+            dispatchers: vec![DispatcherConfig::new("io", 1)],
+            ..ActorSystemConfig::default()
+        });
+        system.init_current();
+
+        // Each actor records the names of the threads that ran it.
+        let names: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+        let recorder = |state: &mut Arc<Mutex<Vec<String>>>, _: ActorId, _: &Message| {
+            let name = thread::current().name().unwrap().to_string();
+            state.lock().unwrap().push(name);
+            Status::Processed
+        };
+        let io_names = Arc::new(Mutex::new(Vec::new()));
+        let io = system
+            .actor_builder()
+            .dispatcher("io")
+            .spawn(io_names.clone(), recorder)
+            .unwrap();
+        let default = system.spawn(names.clone(), recorder);
+        io.send(Message::new(1));
+        default.send(Message::new(1));
+        assert_await_received(&io, 2, 1000);
+        assert_await_received(&default, 2, 1000);
+        assert_eq!(vec!["io-0", "io-0"], *io_names.lock().unwrap());
+        assert_eq!(vec!["default-0", "default-0"], *names.lock().unwrap());
+
+        // An actor blocking the only thread of the io dispatcher doesn't hold up the actors of
+        // the default dispatcher.
+        let gated: Gated = (
+            Arc::new((Mutex::new(false), Condvar::new())),
+            Arc::new(Mutex::new(Vec::new())),
+        );
+        let blocked = system
+            .actor_builder()
+            .dispatcher("io")
+            .spawn(
+                gated.clone(),
+                |state: &mut Gated, _: ActorId, _: &Message| {
+                    let (mutex, condvar) = &*state.0;
+                    let mut open = mutex.lock().unwrap();
+                    while !*open {
+                        open = condvar.wait(open).unwrap();
+                    }
+                    state.1.lock().unwrap().push(0);
+                    Status::Processed
+                },
+            )
+            .unwrap();
+        io.send(Message::new(2));
+        default.send(Message::new(2));
+        assert_await_received(&default, 3, 1000);
+        assert_eq!(2, io.received());
+        open_gate(&gated, 1);
+        assert_await_received(&io, 3, 1000);
+        assert_eq!(1, blocked.received());
+
+        // Spawning on a dispatcher that doesn't exist fails.
+        let result = system
+            .actor_builder()
+            .dispatcher("missing")
+            .spawn(0_usize, simple_handler);
+        assert_eq!(
+            ActorError::UnknownDispatcher("missing".to_string()),
+            result.unwrap_err()
+        );
+
+        // A config can't name another dispatcher after the default one.
+        let config = ActorSystemConfig {
+            dispatchers: vec![DispatcherConfig::new(DEFAULT_DISPATCHER, 1)],
+            ..ActorSystemConfig::default()
+        };
+        assert_eq!(
+            Err(ActorError::DuplicateDispatcher(
+                DEFAULT_DISPATCHER.to_string()
+            )),
+            config.validate()
+        );
+
+        system.trigger_and_await_shutdown();
+    }
+
+    /// The state of a pinned actor which can't be sent to other threads because of the `Rc`.
+    /// It records the names of the threads that use it and that drop it.
+    struct PinnedState {
+        count: Rc<usize>,
+        names: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Drop for PinnedState {
+        fn drop(&mut self) {
+            let name = thread::current().name().unwrap().to_string();
+            self.names.lock().unwrap().push(format!("drop {}", name));
+        }
+    }
+
+    #[test]
+    fn test_pinned_actor() {
+        init_test_log();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+
+        let names: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(Vec::new()));
+        let init_names = names.clone();
+        let aid = system
+            .actor_builder()
+            .name("pinned")
+            .spawn_pinned(
+                move || PinnedState {
+                    count: Rc::new(0),
+                    names: init_names,
+                },
+                |state: &mut PinnedState, _: ActorId, message: &Message| {
+                    if message.content_as::<i32>().is_some() {
+                        state.count = Rc::new(*state.count + 1);
+                        let name = thread::current().name().unwrap().to_string();
+                        state.names.lock().unwrap().push(name);
+                    }
+                    Status::Processed
+                },
+            )
+            .unwrap();
+        for i in 0..3 {
+            aid.send(Message::new(i));
+        }
+        assert_await_received(&aid, 4, 1000);
+        assert_eq!(vec!["pinned-pinned"; 3], *names.lock().unwrap());
+
+        // The name is checked as usual and the thread started for the actor exits.
+        let result = system
+            .actor_builder()
+            .name("pinned")
+            .spawn_pinned(|| 0_usize, simple_handler);
+        assert_eq!(
+            ActorError::NameAlreadyUsed("pinned".to_string()),
+            result.unwrap_err()
+        );
+
+        // The state is dropped on the thread of the actor when the actor stops.
+        system.stop(aid.clone());
+        assert_await(|| names.lock().unwrap().len() == 4, 1000);
+        assert_eq!("drop pinned-pinned", names.lock().unwrap()[3]);
+
+        // A panic creating the state fails the spawn without leaving the actor or its thread.
+        let result = system
+            .actor_builder()
+            .name("panicking")
+            .spawn_pinned(|| -> usize { panic!("No state") }, simple_handler);
+        assert_eq!(
+            ActorError::InitPanicked("No state".to_string()),
+            result.unwrap_err()
+        );
+        assert_eq!(None, system.find_aid_by_name("panicking"));
+
+        // The pinned threads count as actor system threads when shutting down.
+        let aid = system
+            .actor_builder()
+            .spawn_pinned(|| 0_usize, simple_handler)
+            .unwrap();
+        system.trigger_and_await_shutdown();
+        assert!(aid.is_stopped());
+    }
+
+    /// A handler that replies to requests for an `i32` with double the value and ignores
+    /// everything else.
+    fn doubling_handler(_state: &mut usize, _aid: ActorId, message: &Message) -> Status {
+        if let Some(value) = message.content_as::<i32>() {
+            if message.is_request() {
+                message.reply(Message::new(*value * 2)).unwrap();
+            }
+        }
+        Status::Processed
+    }
+
+    #[test]
+    fn test_ask() {
+        init_test_log();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+        let aid = system.spawn(0_usize, doubling_handler);
+
+        let reply = aid.ask(Message::new(11), Duration::from_secs(1)).wait();
+        assert_eq!(22, *reply.unwrap().content_as::<i32>().unwrap());
+
+        // The actor doesn't reply to a bool so the request times out.
+        let start = Instant::now();
+        let reply = aid
+            .ask(Message::new(true), Duration::from_millis(50))
+            .wait();
+        assert_eq!(ActorError::Timeout, reply.err().unwrap());
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert!(system.data.pending_asks.lock().unwrap().is_empty());
+
+        // Only requests can be replied to.
+        assert_eq!(
+            Err(ActorError::ReplyNotExpected),
+            Message::new(11).reply(Message::new(22))
+        );
+
+        // Asking a stopped actor fails right away.
+        system.stop(aid.clone());
+        let reply = aid.ask(Message::new(11), Duration::from_secs(10)).wait();
+        assert_eq!(ActorError::ActorStopped, reply.err().unwrap());
+
+        system.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_ask_from_actor() {
+        init_test_log();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+        let doubler = system.spawn(0_usize, doubling_handler);
+
+        // An actor asks another actor and keeps the reply as its state.
+        let asking = system.spawn(
+            0_i32,
+            move |state: &mut i32, _aid: ActorId, message: &Message| {
+                if let Some(value) = message.content_as::<i32>() {
+                    let handle = doubler.ask(Message::new(*value), Duration::from_secs(1));
+                    let reply = handle.wait().unwrap();
+                    *state = *reply.content_as::<i32>().unwrap();
+                    message.reply(Message::new(*state)).unwrap();
+                }
+                Status::Processed
+            },
+        );
+        let reply = asking.ask(Message::new(21), Duration::from_secs(1)).wait();
+        assert_eq!(42, *reply.unwrap().content_as::<i32>().unwrap());
+
+        system.trigger_and_await_shutdown();
+    }
+
+    /// A minimal executor that blocks the calling thread until the `future` is ready.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        struct ThreadWaker(thread::Thread);
+        impl std::task::Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut context = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            match future.as_mut().poll(&mut context) {
+                Poll::Ready(result) => return result,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_ask_future() {
+        init_test_log();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+        let aid = system.spawn(0_usize, doubling_handler);
+
+        let reply = block_on(aid.ask(Message::new(11), Duration::from_secs(1)));
+        assert_eq!(22, *reply.unwrap().content_as::<i32>().unwrap());
+        let reply = block_on(aid.ask(Message::new(true), Duration::from_millis(50)));
+        assert_eq!(ActorError::Timeout, reply.err().unwrap());
+
+        system.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_node_monitors_removed_when_stopped() {
+        init_test_log();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+
+        // A monitor that can't be sent to for another reason than being stopped is kept.
+        let remote = ActorId {
+            data: Arc::new(ActorIdData {
+                uuid: Uuid::new_v4(),
+                system_uuid: Uuid::new_v4(),
+                name: None,
+                sender: ActorSender::Remote,
+            }),
+        };
+        let stopped = system.spawn(0, simple_handler);
+        system.monitor_nodes(&remote);
+        system.monitor_nodes(&stopped);
+        system.stop(stopped);
+        system.notify_node_monitors(SystemMsg::NodeJoined(Uuid::new_v4()));
+        assert_eq!(vec![remote], *system.data.node_monitors.read().unwrap());
+
+        system.trigger_and_await_shutdown();
+    }
 }
@@ -0,0 +1,1115 @@
+//! Implements the networking that allows actor systems to connect to each other.
+//!
+//! Actor systems are connected with TCP streams. Each stream carries frames, each of which is a
+//! big-endian `u32` length followed by a bincode serialized [`WireMessage`]. The content of the
+//! messages inside is encoded with the [`Codec`] of the actor system. When a connection is
+//! established both sides send a [`WireMessage::Hello`] so that each actor system learns the UUID
+//! of the other and checks that both use the same codec. Once the handshake is done a reader
+//! thread is started for the connection that relays messages for local actors into the local
+//! actor channels.
+//!
+//! The connections of an actor system are also its view of the cluster membership. When an
+//! actor system joins, the members exchange their member lists so that every actor system ends
+//! up connected to every other one. Heartbeats are sent on every connection and a connection
+//! that stays silent for too long is closed, which removes the actor system from the cluster.
+//!
+//! Sending to a remote actor is done through the [`ActorId`] of that actor exactly as it is for
+//! local actors. The [`ActorId`] will look up the connection for the actor system the actor
+//! lives on and write the message to the stream.
+
+use crate::actors::*;
+use crate::codec::*;
+use crate::message::*;
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io;
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// The largest frame in bytes that is written to or read from a connection. A frame announcing
+/// a larger length is rejected before anything is allocated for it.
+const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
+/// The messages that are exchanged between actor systems over a connection.
+#[derive(Serialize, Deserialize)]
+pub(crate) enum WireMessage {
+    /// Sent by both sides as the first frame on a new connection.
+    Hello {
+        /// The UUID of the actor system sending the hello.
+        system_uuid: Uuid,
+        /// A random nonce picked by the sender. The lower of the two nonces exchanged in the
+        /// handshake identifies the connection on both sides.
+        nonce: Uuid,
+        /// The address the actor system sending the hello listens on, if any.
+        listen_address: Option<String>,
+        /// The codec the actor system sending the hello encodes message content with.
+        codec: Codec,
+    },
+
+    /// Sent periodically to let the other side know that this actor system is still alive.
+    Heartbeat,
+
+    /// Informs the other side of the members of the cluster known to the sender as pairs of
+    /// the actor system UUID and the address it listens on.
+    Members(Vec<(Uuid, String)>),
+
+    /// Delivers a message to an actor on the receiving actor system.
+    Deliver {
+        /// The UUID of the actor that should receive the message.
+        actor_uuid: Uuid,
+        /// The message to deliver.
+        message: Message,
+        /// The priority the message was sent with. See [`ActorId::try_send_with_priority`].
+        priority: u8,
+    },
+
+    /// Delivers the reply to a request sent with [`ActorId::ask`] from the receiving actor
+    /// system.
+    Reply {
+        /// The id of the request on the receiving actor system.
+        request_id: Uuid,
+        /// The reply message.
+        message: Message,
+    },
+
+    /// Asks the receiving actor system to inform `monitoring` when its local actor
+    /// `monitored` stops.
+    Monitor {
+        /// The actor that wants to be informed.
+        monitoring: ActorId,
+        /// The actor on the receiving actor system being monitored.
+        monitored: ActorId,
+    },
+
+    /// Informs the receiving actor system that an actor its actors were monitoring stopped.
+    Stopped {
+        /// The actor that stopped.
+        aid: ActorId,
+        /// The reason the actor stopped.
+        reason: StopReason,
+    },
+
+    /// Links the actor `aid` on the receiving actor system to the `linked` actor.
+    Link {
+        /// The actor on the receiving actor system.
+        aid: ActorId,
+        /// The actor it is linked to.
+        linked: ActorId,
+    },
+
+    /// Removes the link from the actor `aid` on the receiving actor system to `linked`.
+    Unlink {
+        /// The actor on the receiving actor system.
+        aid: ActorId,
+        /// The actor it was linked to.
+        linked: ActorId,
+    },
+
+    /// Informs the actor `aid` on the receiving actor system that the linked actor `from`
+    /// stopped.
+    Exit {
+        /// The actor on the receiving actor system.
+        aid: ActorId,
+        /// The linked actor that stopped.
+        from: ActorId,
+        /// The reason the linked actor stopped.
+        reason: StopReason,
+    },
+
+    /// Registers a global name to an actor on every member of the cluster.
+    GlobalRegister {
+        /// The name being registered.
+        name: String,
+        /// The actor the name is registered to.
+        aid: ActorId,
+    },
+
+    /// Removes the registration of a global name from every member of the cluster.
+    GlobalUnregister {
+        /// The name being unregistered.
+        name: String,
+        /// The actor the name was registered to.
+        aid: ActorId,
+    },
+
+    /// Sent to a newly joined member with all of the global names known to the sender.
+    GlobalSync(Vec<(String, ActorId)>),
+}
+
+/// A connection to a remote actor system.
+pub(crate) struct Connection {
+    /// The UUID of the actor system on the other side of the connection.
+    system_uuid: Uuid,
+    /// The address the actor system on the other side listens on, if it listens at all.
+    listen_address: Option<String>,
+    /// Identifies the connection. Both sides of the connection agree on the id.
+    connection_id: Uuid,
+    /// The write side of the stream. Writes are done while holding the lock so that frames
+    /// written by different threads are never interleaved.
+    stream: Mutex<TcpStream>,
+    /// The last time anything was received on the connection.
+    last_received: Mutex<Instant>,
+}
+
+/// The result of registering a new connection with the actor system.
+#[derive(Debug, Eq, PartialEq)]
+pub(crate) enum Registration {
+    /// The remote actor system was not connected before and has now joined the cluster.
+    Joined,
+    /// The remote actor system was already connected and the new connection replaced the
+    /// existing one.
+    Replaced,
+    /// The remote actor system was already connected and the new connection was rejected.
+    Rejected,
+}
+
+impl Connection {
+    /// The UUID of the actor system on the other side of the connection.
+    pub(crate) fn system_uuid(&self) -> Uuid {
+        self.system_uuid
+    }
+
+    /// The id of the connection which is the same on both sides of the connection.
+    pub(crate) fn connection_id(&self) -> Uuid {
+        self.connection_id
+    }
+
+    /// Sends a message over the connection.
+    pub(crate) fn send(&self, wire_message: &WireMessage) -> io::Result<()> {
+        let mut stream = self.stream.lock().unwrap();
+        write_frame(&mut *stream, wire_message)
+    }
+
+    /// Closes the connection which will cause the reader thread to exit.
+    pub(crate) fn close(&self) {
+        let stream = self.stream.lock().unwrap();
+        let _ = stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// Writes a single frame containing the `wire_message` to the `writer`.
+fn write_frame<W: Write>(writer: &mut W, wire_message: &WireMessage) -> io::Result<()> {
+    let bytes = bincode::serialize(wire_message)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    if bytes.len() > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("A frame of {} bytes is too large to write.", bytes.len()),
+        ));
+    }
+    writer.write_all(&(bytes.len() as u32).to_be_bytes())?;
+    writer.write_all(&bytes)?;
+    writer.flush()
+}
+
+/// Reads a single frame from the `reader` and deserializes it into a [`WireMessage`].
+fn read_frame<R: Read>(reader: &mut R) -> io::Result<WireMessage> {
+    let mut length = [0u8; 4];
+    reader.read_exact(&mut length)?;
+    let length = u32::from_be_bytes(length) as usize;
+    if length > MAX_FRAME_SIZE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("A frame of {} bytes is too large to read.", length),
+        ));
+    }
+    let mut bytes = vec![0u8; length];
+    reader.read_exact(&mut bytes)?;
+    bincode::deserialize(&bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Performs the handshake on a newly opened `stream`, registers the resulting connection with
+/// the `system` and starts the reader thread for the connection. Returns the UUID of the
+/// remote actor system. The other side has to answer within the heartbeat timeout.
+pub(crate) fn handshake(system: ActorSystem, mut stream: TcpStream) -> io::Result<Uuid> {
+    stream.set_nodelay(true)?;
+    let timeout = Duration::from_millis(system.config().heartbeat_timeout as u64);
+    stream.set_read_timeout(Some(timeout))?;
+    let nonce = Uuid::new_v4();
+    let hello = WireMessage::Hello {
+        system_uuid: system.uuid(),
+        nonce,
+        listen_address: system.listen_address().map(|a| a.to_string()),
+        codec: system.config().codec,
+    };
+    write_frame(&mut stream, &hello)?;
+    let (system_uuid, remote_nonce, listen_address, codec) = match read_frame(&mut stream)? {
+        WireMessage::Hello {
+            system_uuid,
+            nonce,
+            listen_address,
+            codec,
+        } => (system_uuid, nonce, listen_address, codec),
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Expected a hello as the first message on the connection.",
+            ))
+        }
+    };
+    stream.set_read_timeout(None)?;
+    if system_uuid == system.uuid() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "An actor system cannot connect to itself.",
+        ));
+    }
+    if codec != system.config().codec {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Actor system {} uses the codec {:?} instead of {:?}.",
+                system_uuid,
+                codec,
+                system.config().codec
+            ),
+        ));
+    }
+
+    let connection = Arc::new(Connection {
+        system_uuid,
+        listen_address,
+        connection_id: nonce.min(remote_nonce),
+        stream: Mutex::new(stream.try_clone()?),
+        last_received: Mutex::new(Instant::now()),
+    });
+    match system.register_connection(connection.clone()) {
+        Registration::Rejected => connection.close(),
+        registration => {
+            info!(
+                "Actor system {} connected to {}",
+                system.uuid(),
+                system_uuid
+            );
+            let joined = registration == Registration::Joined;
+            start_reader(system, connection, stream, joined);
+        }
+    }
+    Ok(system_uuid)
+}
+
+/// Starts a thread that reads messages from the `stream` of the `connection` and relays them
+/// to the local actors until the connection is closed. If the connection `joined` a new member
+/// to the cluster the node monitors are informed and the member lists are exchanged.
+fn start_reader(
+    system: ActorSystem,
+    connection: Arc<Connection>,
+    mut stream: TcpStream,
+    joined: bool,
+) {
+    thread::spawn(move || {
+        system.init_current();
+        if joined {
+            system.notify_node_monitors(SystemMsg::NodeJoined(connection.system_uuid()));
+            let global_names = WireMessage::GlobalSync(system.global_names());
+            if let Err(e) = connection.send(&global_names) {
+                warn!(
+                    "Unable to send global names to {}: {:?}",
+                    connection.system_uuid(),
+                    e
+                );
+            }
+            broadcast_members(&system);
+        }
+        loop {
+            let wire_message = read_frame(&mut stream);
+            *connection.last_received.lock().unwrap() = Instant::now();
+            match wire_message {
+                Ok(WireMessage::Deliver {
+                    actor_uuid,
+                    message,
+                    priority,
+                }) => system.deliver_from_remote(&actor_uuid, message, priority),
+                Ok(WireMessage::Reply {
+                    request_id,
+                    message,
+                }) => system.complete_ask(&request_id, Ok(message)),
+                Ok(WireMessage::Heartbeat) => (),
+                Ok(WireMessage::Members(members)) => connect_to_members(&system, members),
+                Ok(WireMessage::Monitor {
+                    monitoring,
+                    monitored,
+                }) => system.monitor_local(monitoring, monitored),
+                Ok(WireMessage::Stopped { aid, reason }) => system.remote_stopped(&aid, reason),
+                Ok(WireMessage::Link { aid, linked }) => system.link_local(&aid, &linked),
+                Ok(WireMessage::Unlink { aid, linked }) => {
+                    system.unlink_local(&aid, &linked);
+                }
+                Ok(WireMessage::Exit { aid, from, reason }) => {
+                    system.linked_exit(&aid, &from, reason)
+                }
+                Ok(WireMessage::GlobalRegister { name, aid }) => {
+                    system.merge_global_name(name, aid)
+                }
+                Ok(WireMessage::GlobalUnregister { name, aid }) => {
+                    system.remove_global_name(&name, &aid)
+                }
+                Ok(WireMessage::GlobalSync(names)) => {
+                    for (name, aid) in names {
+                        system.merge_global_name(name, aid);
+                    }
+                }
+                Ok(WireMessage::Hello { .. }) => {
+                    warn!("Unexpected hello from {}", connection.system_uuid())
+                }
+                Err(e) => {
+                    if !system.is_shutdown_triggered() {
+                        info!("Connection to {} closed: {:?}", connection.system_uuid(), e);
+                    }
+                    break;
+                }
+            }
+        }
+        connection.close();
+        if system.connection_closed(&connection) {
+            system.node_left(&connection.system_uuid());
+        }
+    });
+}
+
+/// Sends the `wire_message` to every member of the cluster the `system` is connected to.
+pub(crate) fn broadcast(system: &ActorSystem, wire_message: &WireMessage) {
+    for connection in system.connections() {
+        if let Err(e) = connection.send(wire_message) {
+            warn!("Unable to send to {}: {:?}", connection.system_uuid(), e);
+        }
+    }
+}
+
+/// Sends the list of members of the cluster known to the `system` to every member so that
+/// the members can connect to each other.
+fn broadcast_members(system: &ActorSystem) {
+    let members: Vec<(Uuid, String)> = system
+        .connections()
+        .iter()
+        .filter_map(|c| c.listen_address.clone().map(|a| (c.system_uuid(), a)))
+        .collect();
+    broadcast(system, &WireMessage::Members(members));
+}
+
+/// Connects the `system` to any of the `members` it is not connected to yet. To avoid both
+/// sides connecting to each other at the same time, only the actor system with the lower
+/// UUID opens the connection.
+fn connect_to_members(system: &ActorSystem, members: Vec<(Uuid, String)>) {
+    for (system_uuid, address) in members {
+        if system.uuid() < system_uuid && !system.is_connected(&system_uuid) {
+            let system = system.clone();
+            thread::spawn(move || {
+                if let Err(e) = system.connect(&address) {
+                    warn!(
+                        "Unable to connect to member {} at {}: {:?}",
+                        system_uuid, address, e
+                    );
+                }
+            });
+        }
+    }
+}
+
+/// The state of a configured seed node as seen by the heartbeat thread.
+enum Seed {
+    /// A thread of its own is connecting to the seed.
+    Connecting,
+    /// The seed was connected to and has the given UUID.
+    Connected(Uuid),
+}
+
+/// Connects to the seed at `address` on a thread of its own so that a seed that can't be
+/// reached doesn't hold up the heartbeats. The outcome is recorded in `seeds`.
+fn connect_to_seed(
+    system: &ActorSystem,
+    address: String,
+    seeds: Arc<Mutex<HashMap<String, Seed>>>,
+) {
+    let system = system.clone();
+    thread::spawn(move || match system.connect(&address) {
+        Ok(system_uuid) => {
+            seeds
+                .lock()
+                .unwrap()
+                .insert(address, Seed::Connected(system_uuid));
+        }
+        Err(e) => {
+            debug!("Unable to connect to seed {}: {:?}", address, e);
+            seeds.lock().unwrap().remove(&address);
+        }
+    });
+}
+
+/// Starts the thread that sends heartbeats to the connected actor systems, closes the
+/// connections to actor systems that haven't been heard from within the heartbeat timeout and
+/// connects to any of the configured seed nodes that the `system` is not connected to.
+pub(crate) fn start_heartbeat(system: ActorSystem) -> JoinHandle<()> {
+    thread::spawn(move || {
+        system.init_current();
+        let config = system.config();
+        let interval = Duration::from_millis(config.heartbeat_interval as u64);
+        let timeout = Duration::from_millis(config.heartbeat_timeout as u64);
+        // The seed nodes keyed by address that are being or have been connected to.
+        let seeds: Arc<Mutex<HashMap<String, Seed>>> = Arc::new(Mutex::new(HashMap::new()));
+        while !system.is_shutdown_triggered() {
+            for address in &config.seed_nodes {
+                let mut seeds_guard = seeds.lock().unwrap();
+                let connect = match seeds_guard.get(address) {
+                    Some(Seed::Connecting) => false,
+                    Some(Seed::Connected(system_uuid)) => !system.is_connected(system_uuid),
+                    None => true,
+                };
+                if connect {
+                    seeds_guard.insert(address.clone(), Seed::Connecting);
+                    connect_to_seed(&system, address.clone(), seeds.clone());
+                }
+            }
+
+            for connection in system.connections() {
+                let last_received = *connection.last_received.lock().unwrap();
+                if last_received.elapsed() > timeout {
+                    warn!(
+                        "No heartbeat from {} in {:?}, closing the connection.",
+                        connection.system_uuid(),
+                        timeout
+                    );
+                    connection.close();
+                } else if let Err(e) = connection.send(&WireMessage::Heartbeat) {
+                    warn!(
+                        "Unable to send heartbeat to {}: {:?}",
+                        connection.system_uuid(),
+                        e
+                    );
+                    connection.close();
+                }
+            }
+            thread::sleep(interval);
+        }
+    })
+}
+
+/// Starts a thread that accepts connections on the `listener` until the `system` is shut down.
+/// The listener is polled every `wait_time` milliseconds so that it can notice the shutdown.
+/// Each accepted connection is handshaken on a thread of its own so that a peer that is slow
+/// to say hello doesn't hold up the connections accepted after it.
+pub(crate) fn start_listener(
+    system: ActorSystem,
+    listener: TcpListener,
+    wait_time: u16,
+) -> io::Result<JoinHandle<()>> {
+    listener.set_nonblocking(true)?;
+    Ok(thread::spawn(move || {
+        system.init_current();
+        while !system.is_shutdown_triggered() {
+            match listener.accept() {
+                Ok((stream, address)) => {
+                    let system = system.clone();
+                    thread::spawn(move || {
+                        let result = stream
+                            .set_nonblocking(false)
+                            .and_then(|_| handshake(system, stream));
+                        if let Err(e) = result {
+                            error!("Handshake with {} failed: {:?}", address, e);
+                        }
+                    });
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(wait_time as u64));
+                }
+                Err(e) => error!("Error accepting connection: {:?}", e),
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+    use std::sync::mpsc;
+
+    #[derive(Serialize, Deserialize)]
+    enum PingPong {
+        Ping(ActorId),
+        Pong,
+    }
+
+    /// Spawns an actor system on another thread that listens on a local port and runs an
+    /// actor that answers pings with pongs and replies to pongs sent as requests. The system,
+    /// its address and the serialized pong `aid` are returned.
+    fn spawn_pong_system() -> (ActorSystem, std::net::SocketAddr, Vec<u8>) {
+        spawn_pong_system_with_config(ActorSystemConfig::default())
+    }
+
+    /// Spawns the actor system of [`spawn_pong_system`] with the given `config`.
+    fn spawn_pong_system_with_config(
+        config: ActorSystemConfig,
+    ) -> (ActorSystem, std::net::SocketAddr, Vec<u8>) {
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let system = ActorSystem::create(config);
+            system.init_current();
+            let address = system.listen("127.0.0.1:0").unwrap();
+            let aid = system.spawn(0, |_state: &mut i32, _aid: ActorId, message: &Message| {
+                if let Some(msg) = message.content_as::<PingPong>() {
+                    match &*msg {
+                        PingPong::Ping(from) => {
+                            assert!(!from.is_local());
+                            from.send(Message::new(PingPong::Pong));
+                        }
+                        PingPong::Pong => message.reply(Message::new(PingPong::Pong)).unwrap(),
+                    }
+                }
+                Status::Processed
+            });
+            let serialized = bincode::serialize(&aid).unwrap();
+            tx.send((system, address, serialized)).unwrap();
+        });
+        rx.recv().unwrap()
+    }
+
+    #[test]
+    fn test_frame_round_trip() {
+        let mut buffer: Vec<u8> = Vec::new();
+        let uuid = Uuid::new_v4();
+        let hello = WireMessage::Hello {
+            system_uuid: uuid,
+            nonce: Uuid::new_v4(),
+            listen_address: None,
+            codec: Codec::Json,
+        };
+        write_frame(&mut buffer, &hello).unwrap();
+        match read_frame(&mut &buffer[..]).unwrap() {
+            WireMessage::Hello {
+                system_uuid, codec, ..
+            } => {
+                assert_eq!(uuid, system_uuid);
+                assert_eq!(Codec::Json, codec);
+            }
+            _ => panic!("Expected a hello."),
+        }
+    }
+
+    #[test]
+    fn test_frame_too_large() {
+        // The length is checked before the content is read.
+        let mut buffer = u32::MAX.to_be_bytes().to_vec();
+        buffer.extend_from_slice(&[0u8; 16]);
+        let error = read_frame(&mut &buffer[..]).err().unwrap();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+
+        // Messages that would make a frame too large can't be sent.
+        let mut buffer: Vec<u8> = Vec::new();
+        let reply = WireMessage::Reply {
+            request_id: Uuid::new_v4(),
+            message: Message::new(vec![0u8; MAX_FRAME_SIZE]),
+        };
+        let error = write_frame(&mut buffer, &reply).err().unwrap();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_send_without_connection() {
+        init_test_log();
+
+        // An aid from a system we are not connected to should fail to send.
+        let (system2, _address, serialized) = spawn_pong_system();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+        let remote: ActorId = bincode::deserialize(&serialized).unwrap();
+        assert!(!remote.is_local());
+        assert_eq!(
+            Err(ActorError::NotConnected(system2.uuid())),
+            remote.try_send(Message::new(11))
+        );
+
+        system.trigger_and_await_shutdown();
+        system2.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_remote_ping_pong() {
+        init_test_log();
+
+        // This test connects two actor systems in the same process over loopback and bounces
+        // a message back and forth between an actor on each of them.
+        let (system2, address, serialized) = spawn_pong_system();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+        assert_eq!(system2.uuid(), system.connect(address).unwrap());
+        assert!(system.is_connected(&system2.uuid()));
+
+        // The aid was deserialized on a system that doesn't host the actor so it is remote.
+        let pong: ActorId = bincode::deserialize(&serialized).unwrap();
+        assert!(!pong.is_local());
+        assert_eq!(system2.uuid(), pong.system_uuid());
+
+        // A message that can't be encoded isn't sent but the connection stays up.
+        let result = pong.try_send(Message::new(vec![0u8; MAX_FRAME_SIZE]));
+        assert!(matches!(result, Err(ActorError::Serialization(_))));
+        assert!(system.is_connected(&system2.uuid()));
+
+        system.spawn(
+            pong,
+            |pong: &mut ActorId, aid: ActorId, message: &Message| {
+                if let Some(msg) = message.content_as::<PingPong>() {
+                    if let PingPong::Pong = &*msg {
+                        ActorSystem::current().trigger_shutdown();
+                    }
+                } else if let Some(msg) = message.content_as::<SystemMsg>() {
+                    if let SystemMsg::Start = &*msg {
+                        pong.send(Message::new(PingPong::Ping(aid)));
+                    }
+                }
+                Status::Processed
+            },
+        );
+
+        system.await_shutdown();
+        system2.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_remote_monitor() {
+        init_test_log();
+
+        // An actor monitoring an actor on another actor system should be informed when the
+        // remote actor is stopped.
+        let (system2, address, serialized) = spawn_pong_system();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+        system.connect(address).unwrap();
+        let pong: ActorId = bincode::deserialize(&serialized).unwrap();
+        let (monitoring, reasons) = spawn_reason_recorder(&system);
+        system.monitor(&monitoring, &pong);
+
+        // The pong travels over the same connection as the monitor request so once the pong
+        // arrives the remote actor system has recorded the monitor.
+        pong.send(Message::new(PingPong::Ping(monitoring.clone())));
+        assert_await_received(&monitoring, 2, 1000);
+        system2.stop(system2.find_aid_by_uuid(&pong.uuid()).unwrap());
+        assert_await_received(&monitoring, 3, 1000);
+        assert_eq!(vec![StopReason::Forced], *reasons.lock().unwrap());
+
+        system.trigger_and_await_shutdown();
+        system2.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_remote_monitor_node_down() {
+        init_test_log();
+
+        // Losing the connection to an actor system should count as the monitored actor
+        // stopping since it can no longer be reached.
+        let (system2, address, serialized) = spawn_pong_system();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+        system.connect(address).unwrap();
+        let pong: ActorId = bincode::deserialize(&serialized).unwrap();
+        let (monitoring, reasons) = spawn_reason_recorder(&system);
+        system.monitor(&monitoring, &pong);
+        pong.send(Message::new(PingPong::Ping(monitoring.clone())));
+        assert_await_received(&monitoring, 2, 1000);
+
+        system.disconnect(&system2.uuid());
+        assert_await_received(&monitoring, 3, 1000);
+
+        // Monitoring an actor on an actor system that is not connected is the same.
+        system.monitor(&monitoring, &pong);
+        assert_await_received(&monitoring, 4, 1000);
+        assert_eq!(
+            vec![StopReason::NodeDown, StopReason::NodeDown],
+            *reasons.lock().unwrap()
+        );
+
+        system.trigger_and_await_shutdown();
+        system2.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_remote_monitor_shutdown() {
+        init_test_log();
+
+        // When the remote actor system shuts down the monitors learn that it was a shutdown
+        // rather than the connection being lost.
+        let (system2, address, serialized) = spawn_pong_system();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+        system.connect(address).unwrap();
+        let pong: ActorId = bincode::deserialize(&serialized).unwrap();
+        let (monitoring, reasons) = spawn_reason_recorder(&system);
+        system.monitor(&monitoring, &pong);
+        pong.send(Message::new(PingPong::Ping(monitoring.clone())));
+        assert_await_received(&monitoring, 2, 1000);
+
+        system2.trigger_and_await_shutdown();
+        assert_await_received(&monitoring, 3, 1000);
+        assert_eq!(vec![StopReason::SystemShutdown], *reasons.lock().unwrap());
+
+        system.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_remote_link() {
+        init_test_log();
+
+        // Links work across actor systems both when the remote actor stops and when the
+        // connection to its actor system is lost.
+        let (system2, address, serialized) = spawn_pong_system();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+        system.connect(address).unwrap();
+        let pong: ActorId = bincode::deserialize(&serialized).unwrap();
+        let (trapping, reasons) = spawn_reason_recorder(&system);
+        system.trap_exit(&trapping, true);
+        system.link(&trapping, &pong);
+        let linked = spawn_crashing(&system);
+        system.link(&linked, &pong);
+
+        // The pong arrives after the link requests so the remote links are in place.
+        pong.send(Message::new(PingPong::Ping(trapping.clone())));
+        assert_await_received(&trapping, 2, 1000);
+        system2.stop(system2.find_aid_by_uuid(&pong.uuid()).unwrap());
+        assert_await_received(&trapping, 3, 1000);
+        assert_await(|| !system.is_alive(&linked), 1000);
+
+        // Stopping a local actor stops the remote actor linked to it.
+        let (system3, address, serialized) = spawn_pong_system();
+        system.connect(address).unwrap();
+        let pong3: ActorId = bincode::deserialize(&serialized).unwrap();
+        let crashing = spawn_crashing(&system);
+        system.link(&crashing, &pong3);
+        system.link(&trapping, &pong3);
+        pong3.send(Message::new(PingPong::Ping(trapping.clone())));
+        assert_await_received(&trapping, 4, 1000);
+        crashing.send(Message::new(11));
+        assert_await_received(&trapping, 5, 1000);
+        assert!(system3.find_aid_by_uuid(&pong3.uuid()).is_none());
+
+        // Losing the connection counts as the remote actor going down.
+        let (system4, address, serialized) = spawn_pong_system();
+        system.connect(address).unwrap();
+        let pong4: ActorId = bincode::deserialize(&serialized).unwrap();
+        system.link(&trapping, &pong4);
+        system.disconnect(&system4.uuid());
+        assert_await_received(&trapping, 6, 1000);
+
+        assert_eq!(
+            vec![
+                StopReason::Forced,
+                StopReason::LinkedExit(crashing.clone()),
+                StopReason::NodeDown
+            ],
+            *reasons.lock().unwrap()
+        );
+
+        system.trigger_and_await_shutdown();
+        system2.trigger_and_await_shutdown();
+        system3.trigger_and_await_shutdown();
+        system4.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_global_names() {
+        init_test_log();
+
+        // A global name registered on one actor system should be found on the others and
+        // released when the actor stops.
+        let (system2, address, serialized) = spawn_pong_system();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+        system.connect(address).unwrap();
+        let pong: ActorId = bincode::deserialize(&serialized).unwrap();
+        let local_pong = system2.find_aid_by_uuid(&pong.uuid()).unwrap();
+        assert_eq!(Ok(()), system2.register_global("pong", &local_pong));
+        assert_await(|| system.find_global("pong").is_some(), 1000);
+        let found = system.find_global("pong").unwrap();
+        assert!(!found.is_local());
+        assert_eq!(pong, found);
+
+        // The name is taken so registering another actor has to fail.
+        let aid = system.spawn(0, |_: &mut i32, _: ActorId, _: &Message| Status::Processed);
+        assert_eq!(
+            Err(ActorError::NameAlreadyUsed("pong".to_string())),
+            system.register_global("pong", &aid)
+        );
+
+        system2.stop(local_pong);
+        assert_await(|| system.find_global("pong").is_none(), 1000);
+        assert_eq!(Ok(()), system.register_global("pong", &aid));
+        assert_await(|| system2.find_global("pong").is_some(), 1000);
+
+        system.unregister_global("pong");
+        assert_await(|| system2.find_global("pong").is_none(), 1000);
+
+        system.trigger_and_await_shutdown();
+        system2.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_global_name_conflict() {
+        init_test_log();
+
+        // Two actor systems that registered the same name while apart should agree on the
+        // owner once they are connected and the loser should be told.
+        let (system2, address, serialized) = spawn_pong_system();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+        let pong_uuid = bincode::deserialize::<ActorId>(&serialized)
+            .map(|aid| aid.uuid())
+            .unwrap();
+        let local_pong = system2.find_aid_by_uuid(&pong_uuid).unwrap();
+        system2.register_global("service", &local_pong).unwrap();
+
+        let aid = system.spawn(0, |_: &mut i32, _: ActorId, message: &Message| {
+            if let Some(msg) = message.content_as::<SystemMsg>() {
+                match &*msg {
+                    SystemMsg::Start => (),
+                    SystemMsg::GlobalNameLost(name) => assert_eq!("service", name),
+                    m => panic!("Unexpected message: {:?}", m),
+                }
+            }
+            Status::Processed
+        });
+        system.register_global("service", &aid).unwrap();
+        system.connect(address).unwrap();
+
+        let local_wins = system.uuid() < system2.uuid();
+        let winner = if local_wins { aid.uuid() } else { pong_uuid };
+        let owner = |s: &ActorSystem| s.find_global("service").map(|a| a.uuid());
+        assert_await(|| owner(&system) == Some(winner), 1000);
+        assert_await(|| owner(&system2) == Some(winner), 1000);
+        if !local_wins {
+            assert_await_received(&aid, 2, 1000);
+        }
+
+        // Names held by actors on an actor system that left the cluster are removed.
+        system2.trigger_and_await_shutdown();
+        if !local_wins {
+            assert_await(|| system.find_global("service").is_none(), 1000);
+        }
+
+        system.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_remote_ask() {
+        init_test_log();
+
+        // A request to a remote actor gets its reply over the connection.
+        let (system2, address, serialized) = spawn_pong_system();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+        system.connect(address).unwrap();
+        let pong: ActorId = bincode::deserialize(&serialized).unwrap();
+
+        let reply = pong.ask(Message::new(PingPong::Pong), Duration::from_secs(1));
+        let reply = reply.wait().unwrap();
+        assert!(matches!(
+            *reply.content_as::<PingPong>().unwrap(),
+            PingPong::Pong
+        ));
+
+        system.trigger_and_await_shutdown();
+        system2.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_slow_handshake() {
+        init_test_log();
+        let (pong_system, address, _) = spawn_pong_system();
+
+        // A peer that connects but never says hello doesn't stop other actor systems from
+        // connecting while the listener waits out its handshake.
+        let _silent = TcpStream::connect(address).unwrap();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        let start = Instant::now();
+        assert_eq!(pong_system.uuid(), system.connect(address).unwrap());
+        let timeout = pong_system.config().heartbeat_timeout as u128;
+        assert!(start.elapsed().as_millis() < timeout);
+
+        system.trigger_and_await_shutdown();
+        pong_system.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_remote_codec() {
+        init_test_log();
+
+        // Actor systems using the same codec other than the default exchange messages.
+        let config = ActorSystemConfig {
+            codec: Codec::MessagePack,
+            ..ActorSystemConfig::default()
+        };
+        let (system2, address, serialized) = spawn_pong_system_with_config(config.clone());
+        let system = ActorSystem::create(config);
+        system.init_current();
+        system.connect(address).unwrap();
+        let pong: ActorId = bincode::deserialize(&serialized).unwrap();
+        let reply = pong.ask(Message::new(PingPong::Pong), Duration::from_secs(1));
+        assert!(matches!(
+            *reply.wait().unwrap().content_as::<PingPong>().unwrap(),
+            PingPong::Pong
+        ));
+
+        // An actor system using another codec is refused.
+        let (system3, address, _) = spawn_pong_system();
+        let error = system.connect(address).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, error.kind());
+        assert!(!system.is_connected(&system3.uuid()));
+
+        system.trigger_and_await_shutdown();
+        system2.trigger_and_await_shutdown();
+        system3.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_node_monitors() {
+        init_test_log();
+
+        // This test verifies that an actor monitoring the nodes gets informed when an actor
+        // system joins the cluster and again when it leaves.
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+        let address = system.listen("127.0.0.1:0").unwrap();
+        let system2 = ActorSystem::create(ActorSystemConfig::default());
+        let uuid2 = system2.uuid();
+
+        let monitor = system.spawn(
+            0,
+            move |state: &mut i32, _aid: ActorId, message: &Message| {
+                if let Some(msg) = message.content_as::<SystemMsg>() {
+                    match &*msg {
+                        SystemMsg::Start => (),
+                        SystemMsg::NodeJoined(uuid) => {
+                            assert_eq!(0, *state);
+                            assert_eq!(uuid2, *uuid);
+                            *state += 1;
+                        }
+                        SystemMsg::NodeLeft(uuid) => {
+                            assert_eq!(1, *state);
+                            assert_eq!(uuid2, *uuid);
+                            *state += 1;
+                        }
+                        m => panic!("Unexpected message: {:?}", m),
+                    }
+                }
+                Status::Processed
+            },
+        );
+        system.monitor_nodes(&monitor);
+
+        assert_eq!(system.uuid(), system2.connect(address).unwrap());
+        assert_await_received(&monitor, 2, 1000);
+        assert_eq!(vec![uuid2], system.members());
+
+        // Shutting down the second actor system closes the connection.
+        system2.trigger_and_await_shutdown();
+        assert_await_received(&monitor, 3, 1000);
+        assert!(system.members().is_empty());
+
+        system.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_seed_discovery() {
+        init_test_log();
+
+        // Two actor systems that only know about a seed should find each other through it.
+        let seed = ActorSystem::create(ActorSystemConfig::default());
+        let seed_address = seed.listen("127.0.0.1:0").unwrap();
+        let config = ActorSystemConfig {
+            listen_address: Some("127.0.0.1:0".to_string()),
+            seed_nodes: vec![seed_address.to_string()],
+            ..ActorSystemConfig::default()
+        };
+        let system1 = ActorSystem::create(config.clone());
+        let system2 = ActorSystem::create(config);
+
+        assert_await(|| system1.is_connected(&system2.uuid()), 5000);
+        assert_await(|| system2.is_connected(&system1.uuid()), 5000);
+        assert_eq!(2, seed.members().len());
+
+        system1.trigger_and_await_shutdown();
+        system2.trigger_and_await_shutdown();
+        seed.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_unreachable_seeds() {
+        init_test_log();
+
+        // One seed accepts connections but never answers the handshake and the other can't
+        // be routed to. Neither should hold up the heartbeats to the connected actor systems.
+        let silent = TcpListener::bind("127.0.0.1:0").unwrap();
+        let system = ActorSystem::create(ActorSystemConfig {
+            heartbeat_interval: 20,
+            seed_nodes: vec![
+                silent.local_addr().unwrap().to_string(),
+                "10.255.255.1:7717".to_string(),
+            ],
+            ..ActorSystemConfig::default()
+        });
+        let address = system.listen("127.0.0.1:0").unwrap();
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_millis(1000)))
+            .unwrap();
+        let hello = WireMessage::Hello {
+            system_uuid: Uuid::new_v4(),
+            nonce: Uuid::new_v4(),
+            listen_address: None,
+            codec: Codec::default(),
+        };
+        write_frame(&mut stream, &hello).unwrap();
+        assert!(matches!(
+            read_frame(&mut stream).unwrap(),
+            WireMessage::Hello { .. }
+        ));
+        let mut heartbeats = 0;
+        while heartbeats < 5 {
+            if let WireMessage::Heartbeat = read_frame(&mut stream).unwrap() {
+                heartbeats += 1;
+            }
+        }
+
+        system.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_heartbeat_timeout() {
+        init_test_log();
+
+        // This test connects to an actor system with a raw socket that completes the handshake
+        // but never sends heartbeats. The actor system should drop the connection.
+        let system = ActorSystem::create(ActorSystemConfig {
+            heartbeat_interval: 20,
+            heartbeat_timeout: 100,
+            ..ActorSystemConfig::default()
+        });
+        let address = system.listen("127.0.0.1:0").unwrap();
+
+        let mut stream = TcpStream::connect(address).unwrap();
+        let uuid = Uuid::new_v4();
+        let hello = WireMessage::Hello {
+            system_uuid: uuid,
+            nonce: Uuid::new_v4(),
+            listen_address: None,
+            codec: Codec::default(),
+        };
+        write_frame(&mut stream, &hello).unwrap();
+        match read_frame(&mut stream).unwrap() {
+            WireMessage::Hello { system_uuid, .. } => assert_eq!(system.uuid(), system_uuid),
+            _ => panic!("Expected a hello."),
+        }
+
+        assert_await(|| system.is_connected(&uuid), 1000);
+        assert_await(|| !system.is_connected(&uuid), 1000);
+
+        system.trigger_and_await_shutdown();
+    }
+}
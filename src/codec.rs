@@ -0,0 +1,154 @@
+//! Implements the serialization formats that messages are sent between actor systems in.
+//!
+//! The content of a [`Message`] sent to an actor on another actor system is encoded with the
+//! [`Codec`] of the sending actor system and decoded with the codec of the receiving actor
+//! system the first time the content is read with [`Message::content_as`]. The codec is chosen
+//! with [`ActorSystemConfig::codec`] and all of the actor systems in a cluster have to use the
+//! same one; a connection between actor systems with different codecs is refused.
+//!
+//! A codec can also be used to serialize a [`Message`] or an [`ActorId`] directly. Both have to
+//! be serialized and deserialized on a thread with a current actor system, see
+//! [`ActorSystem::init_current`]. Message content serialized on a thread without a current
+//! actor system is encoded with the default codec.
+//!
+//! [`Message`]: crate::message::Message
+//! [`Message::content_as`]: crate::message::Message::content_as
+
+use crate::actors::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// The serialization formats that an actor system can send messages in.
+///
+/// # Examples
+/// ```
+/// use axiom::*;
+///
+/// let system = ActorSystem::create(ActorSystemConfig {
+///     codec: Codec::MessagePack,
+///     ..ActorSystemConfig::default()
+/// });
+/// system.init_current();
+///
+/// let bytes = Codec::MessagePack.encode(&Message::new(11)).unwrap();
+/// let message: Message = Codec::MessagePack.decode(&bytes).unwrap();
+/// assert_eq!(11, *message.content_as::<i32>().unwrap());
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub enum Codec {
+    /// The compact binary format of the `bincode` crate. This is the default.
+    #[default]
+    Bincode,
+    /// JSON as written by the `serde_json` crate, which is readable but larger and slower.
+    Json,
+    /// The MessagePack binary format as written by the `rmp-serde` crate.
+    MessagePack,
+}
+
+impl Codec {
+    /// Encodes the `value` into bytes in the format of this codec. If the value can't be
+    /// encoded an [`ActorError::Serialization`] is returned.
+    pub fn encode<T: Serialize + ?Sized>(self, value: &T) -> Result<Vec<u8>, ActorError> {
+        let result = match self {
+            Codec::Bincode => bincode::serialize(value).map_err(|e| e.to_string()),
+            Codec::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+            Codec::MessagePack => rmp_serde::to_vec(value).map_err(|e| e.to_string()),
+        };
+        result.map_err(ActorError::Serialization)
+    }
+
+    /// Decodes a value of type `T` from `bytes` in the format of this codec. If the bytes
+    /// don't hold a `T` an [`ActorError::Serialization`] is returned.
+    pub fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, ActorError> {
+        let result = match self {
+            Codec::Bincode => bincode::deserialize(bytes).map_err(|e| e.to_string()),
+            Codec::Json => serde_json::from_slice(bytes).map_err(|e| e.to_string()),
+            Codec::MessagePack => rmp_serde::from_slice(bytes).map_err(|e| e.to_string()),
+        };
+        result.map_err(ActorError::Serialization)
+    }
+
+    /// The codec of the actor system of the current thread or the default codec if the thread
+    /// doesn't have an actor system.
+    pub(crate) fn current() -> Codec {
+        ActorSystem::try_current()
+            .map(|system| system.config().codec)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::*;
+    use crate::tests::*;
+    use std::thread;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Point {
+        x: i32,
+        label: String,
+    }
+
+    const CODECS: [Codec; 3] = [Codec::Bincode, Codec::Json, Codec::MessagePack];
+
+    #[test]
+    fn test_codec_round_trip() {
+        init_test_log();
+        for codec in CODECS.iter().cloned() {
+            let point = Point {
+                x: 11,
+                label: "eleven".to_string(),
+            };
+            let bytes = codec.encode(&point).unwrap();
+            assert_eq!(point, codec.decode::<Point>(&bytes).unwrap());
+            assert!(matches!(
+                codec.decode::<Point>(&bytes[..1]),
+                Err(ActorError::Serialization(_))
+            ));
+        }
+        assert_eq!(b"11".to_vec(), Codec::Json.encode(&11).unwrap());
+    }
+
+    #[test]
+    fn test_message_codecs() {
+        init_test_log();
+        // Each actor system needs a thread of its own to be the current one.
+        for codec in CODECS.iter().cloned() {
+            thread::spawn(move || {
+                let system = ActorSystem::create(ActorSystemConfig {
+                    codec,
+                    ..ActorSystemConfig::default()
+                });
+                system.init_current();
+                let aid = system.spawn(0, |_: &mut usize, _: ActorId, _: &Message| {
+                    Status::Processed
+                });
+
+                // Actor ids decode to the local actor.
+                let bytes = codec.encode(&aid).unwrap();
+                assert_eq!(aid, codec.decode::<ActorId>(&bytes).unwrap());
+
+                // The content is encoded with the codec of the actor system whatever the
+                // message itself is encoded with. Bincode copies the content bytes as they are.
+                let point = Point {
+                    x: 11,
+                    label: "eleven".to_string(),
+                };
+                let content = codec.encode(&point).unwrap();
+                let bytes = Codec::Bincode.encode(&Message::new(point)).unwrap();
+                assert!(bytes.windows(content.len()).any(|w| w == &content[..]));
+                let message: Message = Codec::Bincode.decode(&bytes).unwrap();
+                assert_eq!(11, message.content_as::<Point>().unwrap().x);
+
+                let bytes = codec.encode(&message).unwrap();
+                let message: Message = codec.decode(&bytes).unwrap();
+                assert_eq!("eleven", message.content_as::<Point>().unwrap().label);
+
+                system.trigger_and_await_shutdown();
+            })
+            .join()
+            .unwrap();
+        }
+    }
+}
@@ -0,0 +1,464 @@
+//! Implements the schedulers that hand actors with messages to the dispatcher threads.
+//!
+//! When an actor gets a message it is scheduled and a dispatcher thread takes it from the
+//! scheduler to process its messages. There are two schedulers to choose from with
+//! [`crate::actors::ActorSystemConfig::scheduler`]. The [`SchedulerKind::GlobalQueue`] puts
+//! every scheduled actor on a single work channel that all of the dispatcher threads take from.
+//! The [`SchedulerKind::WorkStealing`] scheduler gives each dispatcher thread its own queue
+//! that the actors scheduled by that thread go to, so threads mostly work without contending
+//! with each other, and threads that run out of work steal from the queues of the others.
+//!
+//! In both schedulers a dispatcher thread that finds no work parks until an actor is scheduled
+//! or the actor system shuts down rather than polling.
+//!
+//! Besides the default dispatcher, an actor system can have more dispatchers, each with its own
+//! threads and scheduler, declared with [`crate::actors::ActorSystemConfig::dispatchers`].
+//! Actors pick the dispatcher that runs them when they are spawned. This keeps, for example,
+//! actors doing blocking I/O from starving the actors that need the CPU.
+
+use secc::*;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// The kinds of scheduler an actor system can use to hand actors to its dispatcher threads.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum SchedulerKind {
+    /// A single work channel shared by all of the dispatcher threads. The size of the channel
+    /// is set by [`crate::actors::ActorSystemConfig::work_channel_size`].
+    GlobalQueue,
+    /// A queue for each dispatcher thread where the actors scheduled by that thread go, with
+    /// idle threads stealing work from the others. The queues grow as needed so the
+    /// `work_channel_size` is not used.
+    WorkStealing,
+}
+
+/// The name of the dispatcher that runs actors unless they are spawned on another one. Its
+/// threads and scheduler are set by the top level fields of
+/// [`crate::actors::ActorSystemConfig`].
+pub const DEFAULT_DISPATCHER: &str = "default";
+
+/// The configuration of a dispatcher in addition to the default one.
+///
+/// # Examples
+/// ```
+/// use axiom::*;
+///
+/// let system = ActorSystem::create(ActorSystemConfig {
+///     dispatchers: vec![DispatcherConfig::new("io", 8)],
+///     ..ActorSystemConfig::default()
+/// });
+/// system.init_current();
+///
+/// let aid = system
+///     .actor_builder()
+///     .dispatcher("io")
+///     .spawn(0, |_: &mut usize, _: ActorId, _: &Message| Status::Processed)
+///     .unwrap();
+/// ```
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DispatcherConfig {
+    /// The name actors use to pick the dispatcher. It must be unique within the actor system.
+    pub name: String,
+    /// The number of threads of the dispatcher.
+    pub thread_pool_size: u16,
+    /// The scheduler that hands actors to the threads of the dispatcher. The default value is
+    /// [`SchedulerKind::GlobalQueue`].
+    pub scheduler: SchedulerKind,
+    /// The size of the work channel when the scheduler is [`SchedulerKind::GlobalQueue`]. The
+    /// default value is 100.
+    pub work_channel_size: u16,
+}
+
+impl DispatcherConfig {
+    /// Creates the config of a dispatcher with the given `name` and number of threads using
+    /// the default values for the rest.
+    pub fn new(name: &str, thread_pool_size: u16) -> DispatcherConfig {
+        DispatcherConfig {
+            name: name.to_string(),
+            thread_pool_size,
+            scheduler: SchedulerKind::GlobalQueue,
+            work_channel_size: 100,
+        }
+    }
+}
+
+/// A pool of dispatcher threads and the scheduler that hands them actors.
+pub(crate) struct Dispatcher<T: Send + Sync> {
+    /// The name of the dispatcher.
+    pub(crate) name: String,
+    /// The number of threads of the dispatcher.
+    pub(crate) thread_pool_size: u16,
+    /// The scheduler of the dispatcher.
+    pub(crate) scheduler: Box<dyn Scheduler<T>>,
+}
+
+impl<T: Send + Sync + 'static> Dispatcher<T> {
+    /// Creates the dispatcher described by the `config`. The threads are started separately.
+    pub(crate) fn new(config: &DispatcherConfig, poll_ms: u16) -> Dispatcher<T> {
+        Dispatcher {
+            name: config.name.clone(),
+            thread_pool_size: config.thread_pool_size,
+            scheduler: create_scheduler(
+                config.scheduler,
+                config.thread_pool_size,
+                config.work_channel_size,
+                poll_ms,
+            ),
+        }
+    }
+}
+
+/// Gives each scheduler a unique id so that a thread knows which scheduler it works for.
+static NEXT_SCHEDULER_ID: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// The id of the scheduler the current thread is a dispatcher thread of and the index of
+    /// the thread in the pool, if the thread is a dispatcher thread.
+    static WORKER: Cell<Option<(usize, usize)>> = const { Cell::new(None) };
+}
+
+/// A scheduler that hands the scheduled items, which are actors outside of tests, to the
+/// dispatcher threads.
+pub(crate) trait Scheduler<T: Send + Sync>: Send + Sync {
+    /// Registers the calling thread as the dispatcher thread with the given `index`.
+    fn attach(&self, index: usize);
+
+    /// Schedules the `item` to be taken by a dispatcher thread and wakes up a parked thread to
+    /// take it if there is one.
+    fn schedule(&self, item: T);
+
+    /// Takes the next item for the dispatcher thread with the given `index`, parking until
+    /// there is one. Returns `None` once the scheduler is shut down.
+    fn next(&self, index: usize) -> Option<T>;
+
+    /// Shuts the scheduler down and wakes up all parked threads.
+    fn shutdown(&self);
+
+    /// Returns the total number of items that have been scheduled.
+    fn sent(&self) -> usize;
+
+    /// Returns the total number of items that have been taken by the dispatcher threads.
+    fn received(&self) -> usize;
+
+    /// Returns the number of items that are waiting to be taken.
+    fn pending(&self) -> usize {
+        self.sent().saturating_sub(self.received())
+    }
+}
+
+/// Creates a scheduler of the given `kind` for a pool of `threads` dispatcher threads.
+fn create_scheduler<T: Send + Sync + 'static>(
+    kind: SchedulerKind,
+    threads: u16,
+    work_channel_size: u16,
+    poll_ms: u16,
+) -> Box<dyn Scheduler<T>> {
+    match kind {
+        SchedulerKind::GlobalQueue => Box::new(GlobalQueue::new(work_channel_size, poll_ms)),
+        SchedulerKind::WorkStealing => Box::new(WorkStealing::new(threads)),
+    }
+}
+
+/// Holds the dispatcher threads that found no work and parked.
+#[derive(Default)]
+struct Parking {
+    /// The parked threads.
+    threads: Mutex<Vec<thread::Thread>>,
+    /// Set when the scheduler shuts down.
+    shutdown: AtomicBool,
+}
+
+impl Parking {
+    /// Parks the calling thread unless `has_work` says there is work. The thread registers
+    /// itself as parked and checks for work and shutdown once more before parking so that a
+    /// wake up can't be missed; if the wake up comes before the thread parks, parking returns
+    /// right away. Work must be made visible before [`Parking::wake_one`] is called.
+    fn park<F: Fn() -> bool>(&self, has_work: F) {
+        {
+            let mut threads = self.threads.lock().unwrap();
+            if has_work() || self.shutdown.load(Ordering::Relaxed) {
+                return;
+            }
+            threads.push(thread::current());
+        }
+        thread::park();
+        // The thread may wake up spuriously in which case it is still listed as parked.
+        let id = thread::current().id();
+        self.threads.lock().unwrap().retain(|t| t.id() != id);
+    }
+
+    /// Wakes up one parked thread, if there is one.
+    fn wake_one(&self) {
+        if let Some(thread) = self.threads.lock().unwrap().pop() {
+            thread.unpark();
+        }
+    }
+
+    /// Marks the scheduler as shut down and wakes up all of the parked threads.
+    fn shutdown(&self) {
+        self.shutdown.store(true, Ordering::Relaxed);
+        for thread in self.threads.lock().unwrap().drain(..) {
+            thread.unpark();
+        }
+    }
+
+    /// Checks whether the scheduler was shut down.
+    fn is_shutdown(&self) -> bool {
+        self.shutdown.load(Ordering::Relaxed)
+    }
+}
+
+/// A scheduler with a single work channel shared by all of the dispatcher threads.
+struct GlobalQueue<T: Send + Sync> {
+    /// Sender side of the work channel.
+    sender: SeccSender<T>,
+    /// Receiver side of the work channel.
+    receiver: SeccReceiver<T>,
+    /// The threads waiting for work.
+    parking: Parking,
+}
+
+impl<T: Send + Sync> GlobalQueue<T> {
+    /// Creates the scheduler with a work channel of the given `size`.
+    fn new(size: u16, poll_ms: u16) -> GlobalQueue<T> {
+        let (sender, receiver) = secc::create::<T>(size, poll_ms);
+        GlobalQueue {
+            sender,
+            receiver,
+            parking: Parking::default(),
+        }
+    }
+}
+
+impl<T: Send + Sync> Scheduler<T> for GlobalQueue<T> {
+    fn attach(&self, _index: usize) {}
+
+    fn schedule(&self, item: T) {
+        self.sender
+            .send_await(item)
+            .expect("Unable to schedule on the work channel");
+        self.parking.wake_one();
+    }
+
+    fn next(&self, _index: usize) -> Option<T> {
+        while !self.parking.is_shutdown() {
+            match self.receiver.receive() {
+                Ok(item) => return Some(item),
+                Err(_) => self.parking.park(|| self.receiver.receivable() > 0),
+            }
+        }
+        None
+    }
+
+    fn shutdown(&self) {
+        self.parking.shutdown();
+    }
+
+    fn sent(&self) -> usize {
+        self.receiver.sent()
+    }
+
+    fn received(&self) -> usize {
+        self.receiver.received()
+    }
+}
+
+/// A scheduler where each dispatcher thread has its own queue and idle threads steal work.
+struct WorkStealing<T: Send + Sync> {
+    /// The unique id of this scheduler.
+    id: usize,
+    /// The queues of the dispatcher threads. A thread takes from the front of its own queue
+    /// and steals from the back of the others.
+    queues: Vec<Mutex<VecDeque<T>>>,
+    /// The queue for items scheduled by threads that are not dispatcher threads.
+    injector: Mutex<VecDeque<T>>,
+    /// The total number of items scheduled.
+    sent: AtomicUsize,
+    /// The total number of items taken by dispatcher threads.
+    received: AtomicUsize,
+    /// The threads waiting for work.
+    parking: Parking,
+}
+
+impl<T: Send + Sync> WorkStealing<T> {
+    /// Creates the scheduler for a pool of `threads` dispatcher threads.
+    fn new(threads: u16) -> WorkStealing<T> {
+        WorkStealing {
+            id: NEXT_SCHEDULER_ID.fetch_add(1, Ordering::Relaxed),
+            queues: (0..threads.max(1))
+                .map(|_| Mutex::new(VecDeque::new()))
+                .collect(),
+            injector: Mutex::new(VecDeque::new()),
+            sent: AtomicUsize::new(0),
+            received: AtomicUsize::new(0),
+            parking: Parking::default(),
+        }
+    }
+
+    /// Takes an item from the queue of the thread with the given `index`, then from the
+    /// injector and finally by stealing half of the items from the queue of another thread.
+    fn find_work(&self, index: usize) -> Option<T> {
+        if let Some(item) = self.queues[index].lock().unwrap().pop_front() {
+            return Some(item);
+        }
+        if let Some(item) = self.injector.lock().unwrap().pop_front() {
+            return Some(item);
+        }
+        let count = self.queues.len();
+        for offset in 1..count {
+            let victim = (index + offset) % count;
+            let stolen: Vec<T> = {
+                let mut queue = self.queues[victim].lock().unwrap();
+                let take = queue.len().div_ceil(2);
+                let keep = queue.len() - take;
+                queue.drain(keep..).collect()
+            };
+            let mut stolen = stolen.into_iter();
+            if let Some(item) = stolen.next() {
+                self.queues[index].lock().unwrap().extend(stolen);
+                return Some(item);
+            }
+        }
+        None
+    }
+}
+
+impl<T: Send + Sync> Scheduler<T> for WorkStealing<T> {
+    fn attach(&self, index: usize) {
+        WORKER.with(|worker| worker.set(Some((self.id, index))));
+    }
+
+    fn schedule(&self, item: T) {
+        // The count goes up first so that it is never behind the count of received items.
+        self.sent.fetch_add(1, Ordering::SeqCst);
+        match WORKER.with(|worker| worker.get()) {
+            Some((id, index)) if id == self.id => {
+                self.queues[index].lock().unwrap().push_back(item)
+            }
+            _ => self.injector.lock().unwrap().push_back(item),
+        }
+        self.parking.wake_one();
+    }
+
+    fn next(&self, index: usize) -> Option<T> {
+        while !self.parking.is_shutdown() {
+            match self.find_work(index) {
+                Some(item) => {
+                    self.received.fetch_add(1, Ordering::SeqCst);
+                    return Some(item);
+                }
+                None => self.parking.park(|| self.pending() > 0),
+            }
+        }
+        None
+    }
+
+    fn shutdown(&self) {
+        self.parking.shutdown();
+    }
+
+    fn sent(&self) -> usize {
+        self.sent.load(Ordering::SeqCst)
+    }
+
+    fn received(&self) -> usize {
+        self.received.load(Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    /// Starts a dispatcher thread with the given `index` that takes items from the `scheduler`
+    /// until it shuts down and returns the items it took.
+    fn start_worker(
+        scheduler: Arc<Box<dyn Scheduler<usize>>>,
+        index: usize,
+    ) -> thread::JoinHandle<Vec<usize>> {
+        thread::spawn(move || {
+            scheduler.attach(index);
+            let mut taken = Vec::new();
+            while let Some(item) = scheduler.next(index) {
+                taken.push(item);
+            }
+            taken
+        })
+    }
+
+    /// Schedules items on a scheduler of the given `kind` and checks that every item is taken
+    /// exactly once and that shutting down wakes up the parked threads.
+    fn assert_schedules_all(kind: SchedulerKind) {
+        let scheduler: Arc<Box<dyn Scheduler<usize>>> =
+            Arc::new(create_scheduler(kind, 3, 100, 10));
+        let workers: Vec<_> = (0..3)
+            .map(|index| start_worker(scheduler.clone(), index))
+            .collect();
+        for item in 0..50 {
+            scheduler.schedule(item);
+        }
+        while scheduler.pending() > 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(50, scheduler.sent());
+        assert_eq!(50, scheduler.received());
+
+        // The threads are parked by now and are woken up by the shutdown.
+        thread::sleep(Duration::from_millis(20));
+        scheduler.shutdown();
+        let mut taken: Vec<usize> = workers
+            .into_iter()
+            .flat_map(|w| w.join().unwrap())
+            .collect();
+        taken.sort();
+        assert_eq!((0..50).collect::<Vec<usize>>(), taken);
+    }
+
+    #[test]
+    fn test_global_queue() {
+        assert_schedules_all(SchedulerKind::GlobalQueue);
+    }
+
+    #[test]
+    fn test_work_stealing() {
+        assert_schedules_all(SchedulerKind::WorkStealing);
+    }
+
+    #[test]
+    fn test_local_queue_and_stealing() {
+        let scheduler: WorkStealing<usize> = WorkStealing::new(2);
+
+        // Items scheduled by a dispatcher thread go to its own queue.
+        scheduler.attach(0);
+        for item in 0..4 {
+            scheduler.schedule(item);
+        }
+        assert_eq!(4, scheduler.queues[0].lock().unwrap().len());
+        assert!(scheduler.injector.lock().unwrap().is_empty());
+
+        // The owner takes from the front while a thief steals half from the back.
+        assert_eq!(Some(0), scheduler.next(0));
+        assert_eq!(Some(2), scheduler.next(1));
+        assert_eq!(
+            vec![3],
+            Vec::from(scheduler.queues[1].lock().unwrap().clone())
+        );
+        assert_eq!(
+            vec![1],
+            Vec::from(scheduler.queues[0].lock().unwrap().clone())
+        );
+
+        // Items scheduled by another thread go to the injector.
+        thread::scope(|scope| {
+            scope.spawn(|| scheduler.schedule(9));
+        });
+        assert_eq!(1, scheduler.injector.lock().unwrap().len());
+        WORKER.with(|worker| worker.set(None));
+    }
+}
@@ -106,6 +106,42 @@
 //! you could use it in an actor as state by declaring your own handler function and making the
 //! calls to the 3rd party structure.
 //!
+//! Writing the `handle` function that dispatches on the type of the message is routine, so the
+//! [`processor`] attribute can write it instead. Marking the handler methods of the `impl` block
+//! with `#[handler]` generates the same `handle` function, and declaring two handlers for the
+//! same type is a compile error rather than a handler that is never called:
+//!
+//! ```rust
+//! use axiom::*;
+//!
+//! let system = ActorSystem::create(ActorSystemConfig::default());
+//! system.init_current(); // Needed to call from outside of actor system threads.
+//!
+//! struct Data {
+//!     value: i32,
+//! }
+//!
+//! #[processor]
+//! impl Data {
+//!     #[handler]
+//!     fn handle_bool(&mut self, _aid: ActorId, message: &bool) -> Status {
+//!         self.value += if *message { 1 } else { -1 };
+//!         Status::Processed
+//!     }
+//!
+//!     #[handler]
+//!     fn handle_i32(&mut self, _aid: ActorId, message: &i32) -> Status {
+//!         self.value += *message;
+//!         Status::Processed
+//!     }
+//! }
+//!
+//! let aid = system.spawn(Data { value: 0 }, Data::handle);
+//!
+//! aid.send(Message::new(11));
+//! aid.send(Message::new(true));
+//! ```
+//!
 //! *It's important to keep in mind that the starting state is moved into the actor and you
 //! will not have external access to it afterwards.* This is by design and although you could
 //! conceivably use a [`std::sync::Arc`] to a structure as state, that would definitely be a bad
@@ -116,22 +152,55 @@
 //! act as a set of examples for users of Axiom.
 //!
 
+// Lets the code generated by the macros refer to `::axiom` within this crate as well.
+extern crate self as axiom;
+
 pub mod actors;
+mod cluster;
+pub mod codec;
+pub mod dispatcher;
+pub mod lifecycle;
 pub mod message;
+pub mod supervisor;
+pub mod timers;
+pub mod typed;
 
+pub use crate::actors::ActorBuilder;
+pub use crate::actors::ActorConfig;
 pub use crate::actors::ActorError;
 pub use crate::actors::ActorId;
 pub use crate::actors::ActorSystem;
 pub use crate::actors::ActorSystemConfig;
+pub use crate::actors::AskHandle;
+pub use crate::actors::OverflowPolicy;
 pub use crate::actors::Status;
+pub use crate::actors::StopReason;
 pub use crate::actors::SystemMsg;
+pub use crate::codec::Codec;
+pub use crate::dispatcher::DispatcherConfig;
+pub use crate::dispatcher::SchedulerKind;
+pub use crate::dispatcher::DEFAULT_DISPATCHER;
+pub use crate::lifecycle::Actor;
 pub use crate::message::Message;
+pub use crate::supervisor::ChildFactory;
+pub use crate::supervisor::RestartStrategy;
+pub use crate::supervisor::SupervisorConfig;
+pub use crate::timers::Clock;
+pub use crate::timers::SystemClock;
+pub use crate::timers::TimerHandle;
+pub use crate::timers::VirtualClock;
+pub use crate::typed::TypedActorId;
+pub use crate::typed::TypedProcessor;
+pub use axiom_derive::processor;
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use log::LevelFilter;
     use serde::{Deserialize, Serialize};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
 
     pub fn init_test_log() {
         let _ = env_logger::builder()
@@ -140,6 +209,76 @@ mod tests {
             .try_init();
     }
 
+    /// A test helper to assert that a certain number of messages arrived in a certain time.
+    #[allow(clippy::assertions_on_constants)]
+    pub fn assert_await_received(aid: &ActorId, count: u8, timeout_ms: u64) {
+        let start = Instant::now();
+        let duration = Duration::from_millis(timeout_ms);
+        while aid.received() < count as usize {
+            if Instant::elapsed(&start) > duration {
+                assert!(
+                    false,
+                    "Timed out! count: {} timeout_ms: {}",
+                    count, timeout_ms
+                )
+            }
+        }
+    }
+
+    /// A test helper that waits up to `timeout_ms` for the `condition` to become true.
+    pub fn assert_await<F: Fn() -> bool>(condition: F, timeout_ms: u64) {
+        let start = Instant::now();
+        while !condition() {
+            if start.elapsed() > Duration::from_millis(timeout_ms) {
+                panic!("Timed out after {} ms", timeout_ms);
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    /// Spawns an actor that records the reasons in the stopped and exit messages it is sent.
+    pub fn spawn_reason_recorder(system: &ActorSystem) -> (ActorId, Arc<Mutex<Vec<StopReason>>>) {
+        let reasons: Arc<Mutex<Vec<StopReason>>> = Arc::new(Mutex::new(Vec::new()));
+        let aid = system.spawn(
+            reasons.clone(),
+            |state: &mut Arc<Mutex<Vec<StopReason>>>, _: ActorId, message: &Message| {
+                if let Some(msg) = message.content_as::<SystemMsg>() {
+                    match &*msg {
+                        SystemMsg::Stopped { reason, .. } | SystemMsg::Exit { reason, .. } => {
+                            state.lock().unwrap().push(reason.clone())
+                        }
+                        _ => (),
+                    }
+                }
+                Status::Processed
+            },
+        );
+        (aid, reasons)
+    }
+
+    /// A processor that panics when it is sent an `i32` and stops normally when it is sent a
+    /// `bool`.
+    fn crashing(_: &mut usize, _: ActorId, message: &Message) -> Status {
+        if message.content_as::<i32>().is_some() {
+            panic!("Crashing as requested.");
+        } else if message.content_as::<bool>().is_some() {
+            Status::Stop
+        } else {
+            Status::Processed
+        }
+    }
+
+    /// Spawns an actor that panics when it is sent an `i32` and stops normally when it is sent
+    /// a `bool`.
+    pub fn spawn_crashing(system: &ActorSystem) -> ActorId {
+        system.spawn(0_usize, crashing)
+    }
+
+    /// Spawns an actor like [`spawn_crashing`] but registered under the given `name`.
+    pub fn spawn_named_crashing(system: &ActorSystem, name: &str) -> ActorId {
+        system.spawn_named(name, 0_usize, crashing).unwrap()
+    }
+
     #[derive(Serialize, Deserialize)]
     enum PingPong {
         Ping(ActorId),
@@ -184,6 +323,61 @@ mod tests {
         }
     }
 
+    /// State whose handlers are dispatched to by the [`processor`] macro, recording what they
+    /// were sent.
+    struct Dispatched {
+        events: Arc<Mutex<Vec<String>>>,
+    }
+
+    #[processor(dispatch)]
+    impl Dispatched {
+        #[handler]
+        fn handle_i32(&mut self, _: ActorId, message: &i32) -> Status {
+            self.events.lock().unwrap().push(format!("i32 {}", message));
+            Status::Processed
+        }
+
+        #[handler]
+        fn handle_string(&mut self, _: ActorId, message: &String) -> Status {
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("String {}", message));
+            Status::Processed
+        }
+
+        #[handler]
+        fn handle_other(&mut self, _: ActorId, message: &Message) -> Status {
+            if message.content_as::<SystemMsg>().is_none() {
+                self.events.lock().unwrap().push("other".to_string());
+            }
+            Status::Processed
+        }
+    }
+
+    #[test]
+    fn test_processor_macro() {
+        init_test_log();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let state = Dispatched {
+            events: events.clone(),
+        };
+        let aid = system.spawn(state, Dispatched::dispatch);
+        aid.send(Message::new(11));
+        aid.send(Message::new("hello".to_string()));
+        aid.send(Message::new(true));
+        assert_await_received(&aid, 4, 1000);
+        assert_eq!(
+            vec!["i32 11", "String hello", "other"],
+            *events.lock().unwrap()
+        );
+
+        system.trigger_and_await_shutdown();
+    }
+
     #[test]
     fn test_ping_pong() {
         let system = ActorSystem::create(ActorSystemConfig::default());
@@ -0,0 +1,257 @@
+//! Implements actors defined by a trait with hooks for the stages of their lifecycle.
+//!
+//! Instead of a processor function that has to pick the [`SystemMsg::Start`] and
+//! [`SystemMsg::Stop`] out of its messages, an actor can be a type implementing [`Actor`],
+//! spawned with [`ActorSystem::spawn_actor`] or [`ActorBuilder::spawn_actor`]. The actor system
+//! calls [`Actor::pre_start`] for the start message, [`Actor::handle`] for every other message
+//! and [`Actor::post_stop`] once the actor stopped, whatever the reason it stopped for.
+//!
+//! When [`Actor::handle`] panics the actor is asked with [`Actor::pre_restart`] whether it wants
+//! to restart. An actor that restarts drops the message that caused the panic, runs
+//! [`Actor::pre_start`] again and goes on with the next message. Otherwise the actor stops as
+//! any actor that panics does and its supervisor, if any, can restart it from scratch.
+
+use crate::actors::*;
+use crate::message::*;
+use log::warn;
+use std::panic::{self, AssertUnwindSafe};
+
+/// An actor implemented as a type with a hook for each stage of its lifecycle. Only
+/// [`Actor::handle`] has to be implemented. The value is the state of the actor.
+///
+/// # Examples
+/// ```
+/// use axiom::*;
+///
+/// struct Counter {
+///     count: usize,
+/// }
+///
+/// impl Actor for Counter {
+///     fn pre_start(&mut self, _aid: ActorId) -> Status {
+///         self.count = 0;
+///         Status::Processed
+///     }
+///
+///     fn handle(&mut self, _aid: ActorId, message: &Message) -> Status {
+///         if let Some(value) = message.content_as::<usize>() {
+///             self.count += *value;
+///         }
+///         Status::Processed
+///     }
+///
+///     fn post_stop(&mut self, _aid: ActorId, reason: &StopReason) {
+///         println!("Counted to {} and stopped: {:?}", self.count, reason);
+///     }
+/// }
+///
+/// let system = ActorSystem::create(ActorSystemConfig::default());
+/// system.init_current();
+///
+/// let aid = system.spawn_actor(Counter { count: 0 });
+/// aid.send(Message::new(11_usize));
+/// ```
+pub trait Actor: Send + Sync + 'static {
+    /// Called for the start message before the actor handles any other message and again
+    /// after a restart. The returned status is handled as it is for any message so returning
+    /// [`Status::Stop`] stops the actor. The default does nothing.
+    fn pre_start(&mut self, _aid: ActorId) -> Status {
+        Status::Processed
+    }
+
+    /// Handles a message sent to the actor, which can be any message other than a
+    /// [`SystemMsg::Start`] or a [`SystemMsg::Stop`].
+    fn handle(&mut self, aid: ActorId, message: &Message) -> Status;
+
+    /// Called once when the actor stopped for the given `reason`, after the last message it
+    /// handled. The actor can no longer be sent messages at this point. The default does
+    /// nothing.
+    fn post_stop(&mut self, _aid: ActorId, _reason: &StopReason) {}
+
+    /// Called when [`Actor::handle`] panicked with the message of the panic. Returns whether
+    /// to restart the actor rather than stopping it with a [`StopReason::Panic`]. An actor
+    /// that restarts should reset any state the panic could have left broken, either here or in
+    /// [`Actor::pre_start`] which is called next. The default doesn't restart.
+    fn pre_restart(&mut self, _aid: ActorId, _panic: &str) -> bool {
+        false
+    }
+}
+
+/// The handler of an [`Actor`] that calls its hooks.
+struct Lifecycle<A: Actor>(A);
+
+impl<A: Actor> Handler for Lifecycle<A> {
+    fn handle(&mut self, aid: ActorId, message: &Message) -> Status {
+        if let Some(msg) = message.content_as::<SystemMsg>() {
+            match *msg {
+                SystemMsg::Start => return self.0.pre_start(aid),
+                SystemMsg::Stop => return Status::Stop,
+                _ => (),
+            }
+        }
+        let actor = &mut self.0;
+        match panic::catch_unwind(AssertUnwindSafe(|| actor.handle(aid.clone(), message))) {
+            Ok(status) => status,
+            Err(payload) => {
+                let message_text = panic_message(&*payload);
+                if !actor.pre_restart(aid.clone(), &message_text) {
+                    panic::resume_unwind(payload);
+                }
+                warn!("Actor {:?} panicked, restarting: {}", aid, message_text);
+                match actor.pre_start(aid) {
+                    Status::Stop => Status::Stop,
+                    _ => Status::Processed,
+                }
+            }
+        }
+    }
+
+    fn stopped(&mut self, aid: ActorId, reason: &StopReason) {
+        self.0.post_stop(aid, reason);
+    }
+}
+
+impl ActorBuilder {
+    /// Spawns the given [`Actor`] with the options set on the builder. Otherwise this is the
+    /// same as [`ActorBuilder::spawn`].
+    pub fn spawn_actor<A: Actor>(self, actor: A) -> Result<ActorId, ActorError> {
+        self.spawn_handler(Box::new(Lifecycle(actor)))
+    }
+}
+
+impl ActorSystem {
+    /// Spawns a new unnamed actor on the system from the given [`Actor`].
+    pub fn spawn_actor<A: Actor>(&self, actor: A) -> ActorId {
+        self.actor_builder().spawn_actor(actor).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+    use std::sync::{Arc, Mutex};
+
+    /// An actor that records the hooks that are called. It panics when sent `0`, restarting
+    /// if it was created to restart, and stops itself from within `handle` when sent `-1`.
+    struct Recorder {
+        events: Arc<Mutex<Vec<String>>>,
+        restart: bool,
+    }
+
+    impl Recorder {
+        fn spawn(system: &ActorSystem, restart: bool) -> (ActorId, Arc<Mutex<Vec<String>>>) {
+            let events = Arc::new(Mutex::new(Vec::new()));
+            let aid = system.spawn_actor(Recorder {
+                events: events.clone(),
+                restart,
+            });
+            (aid, events)
+        }
+
+        fn record(&self, event: String) {
+            self.events.lock().unwrap().push(event);
+        }
+    }
+
+    impl Actor for Recorder {
+        fn pre_start(&mut self, _aid: ActorId) -> Status {
+            self.record("pre_start".to_string());
+            Status::Processed
+        }
+
+        fn handle(&mut self, aid: ActorId, message: &Message) -> Status {
+            if let Some(value) = message.content_as::<i32>() {
+                match *value {
+                    0 => panic!("zero"),
+                    -1 => ActorSystem::current().stop(aid),
+                    value => self.record(format!("handle {}", value)),
+                }
+            } else {
+                self.record(format!("{:?}", *message.content_as::<SystemMsg>().unwrap()));
+            }
+            Status::Processed
+        }
+
+        fn post_stop(&mut self, _aid: ActorId, reason: &StopReason) {
+            self.record(format!("post_stop {:?}", reason));
+        }
+
+        fn pre_restart(&mut self, _aid: ActorId, panic: &str) -> bool {
+            self.record(format!("pre_restart {}", panic));
+            self.restart
+        }
+    }
+
+    #[test]
+    fn test_lifecycle_hooks() {
+        init_test_log();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+
+        // The start and stop messages go to the hooks.
+        let (aid, events) = Recorder::spawn(&system, false);
+        aid.send(Message::new(1));
+        assert_await_received(&aid, 2, 1000);
+        aid.send(Message::new(SystemMsg::GlobalNameLost("name".to_string())));
+        aid.send(Message::new(SystemMsg::Stop));
+        assert_await(|| events.lock().unwrap().len() == 4, 1000);
+        assert!(!system.is_alive(&aid));
+        assert_eq!(
+            vec![
+                "pre_start",
+                "handle 1",
+                "GlobalNameLost(\"name\")",
+                "post_stop Normal"
+            ],
+            *events.lock().unwrap()
+        );
+
+        // The actor is told about being stopped from the outside and from within `handle`.
+        let (aid, events) = Recorder::spawn(&system, false);
+        assert_await_received(&aid, 1, 1000);
+        system.stop(aid.clone());
+        assert_eq!("post_stop Forced", events.lock().unwrap()[1]);
+        let (aid, events) = Recorder::spawn(&system, false);
+        aid.send(Message::new(-1));
+        assert_await(|| events.lock().unwrap().len() == 2, 1000);
+        assert_eq!("post_stop Forced", events.lock().unwrap()[1]);
+        assert!(!system.is_alive(&aid));
+
+        system.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_lifecycle_restart() {
+        init_test_log();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+
+        // An actor that doesn't restart stops with the panic.
+        let (aid, events) = Recorder::spawn(&system, false);
+        aid.send(Message::new(0));
+        assert_await(|| events.lock().unwrap().len() == 3, 1000);
+        assert_eq!(
+            vec!["pre_start", "pre_restart zero", "post_stop Panic(\"zero\")"],
+            *events.lock().unwrap()
+        );
+        assert!(!system.is_alive(&aid));
+
+        // An actor that restarts starts again and goes on with the next message.
+        let (aid, events) = Recorder::spawn(&system, true);
+        aid.send(Message::new(0));
+        aid.send(Message::new(2));
+        assert_await_received(&aid, 3, 1000);
+        assert_eq!(
+            vec!["pre_start", "pre_restart zero", "pre_start", "handle 2"],
+            *events.lock().unwrap()
+        );
+        assert!(system.is_alive(&aid));
+
+        system.trigger_and_await_shutdown();
+        assert_eq!(
+            "post_stop SystemShutdown",
+            events.lock().unwrap().last().unwrap()
+        );
+    }
+}
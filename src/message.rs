@@ -1,14 +1,18 @@
+use crate::actors::{ActorError, ActorId, ActorSystem, SystemMsg};
+use crate::codec::Codec;
+use log::{error, warn};
+use once_cell::sync::Lazy;
 use serde::de::DeserializeOwned;
+use serde::ser::{Error, SerializeStruct};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
-use std::any::{Any, TypeId};
-use std::collections::hash_map::DefaultHasher;
-use std::hash::Hash;
-use std::hash::Hasher;
+use std::any::{self, Any, TypeId};
+use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
+use uuid::Uuid;
 
 pub trait ActorMessage: Send + Sync + Any {
-    /// Get a JSON representation of `self`.
-    fn to_bincode(&self) -> Vec<u8>;
+    /// Encodes `self` into bytes with the given `codec`.
+    fn encode(&self, codec: Codec) -> Result<Vec<u8>, ActorError>;
 }
 
 impl dyn ActorMessage {
@@ -26,23 +30,60 @@ impl dyn ActorMessage {
     }
 }
 
+#[allow(clippy::needless_maybe_sized)]
 impl<T: 'static> ActorMessage for T
 where
     T: Serialize + DeserializeOwned + Sync + Send + Any + ?Sized,
 {
-    fn to_bincode(&self) -> Vec<u8> {
-        bincode::serialize(self).unwrap()
+    fn encode(&self, codec: Codec) -> Result<Vec<u8>, ActorError> {
+        codec.encode(self)
     }
 }
 
+/// The names of the message types, registered with [`Message::register_type`] or else taken
+/// from [`std::any::type_name`] the first time the type is used in a message. The message
+/// types of Axiom itself have fixed names so that they don't depend on how Axiom was built.
+static TYPE_NAMES: Lazy<RwLock<TypeNames>> = Lazy::new(|| {
+    let mut type_names = TypeNames::default();
+    type_names.insert(TypeId::of::<SystemMsg>(), "axiom.SystemMsg");
+    type_names.insert(TypeId::of::<ActorId>(), "axiom.ActorId");
+    RwLock::new(type_names)
+});
+
+/// Maps the message types to the hashes of their names and back.
+#[derive(Default)]
+struct TypeNames {
+    /// The hash of the name of each type that has been named.
+    hashes: HashMap<TypeId, u64>,
+    /// The type that has the name with each hash in `hashes`.
+    types: HashMap<u64, TypeId>,
+}
+
+impl TypeNames {
+    /// Gives the type with the given id the `name`, which no other type may have.
+    fn insert(&mut self, type_id: TypeId, name: &str) {
+        let hash = fnv1a(name);
+        self.hashes.insert(type_id, hash);
+        self.types.insert(hash, type_id);
+    }
+}
+
+/// Hashes the `name` of a message type with the 64 bit FNV-1a hash. Unlike the hashers of the
+/// standard library this gives the same hash in every build on every platform.
+fn fnv1a(name: &str) -> u64 {
+    name.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
 /// The message content in a message.
 pub enum MessageContent {
     // FIXME Investigate if it is possible to get rid of the inner arc since the message will
     // always be in an Arc anyway.
     /// The message is a local message.
     Local(Arc<dyn ActorMessage + 'static>),
-    /// The message is from remote and has the given hash of a [`std::any::TypeId`] and the
-    /// serialized content.
+    /// The message is from remote and has the content encoded with the [`Codec`] of the actor
+    /// system that sent it.
     Remote(Vec<u8>),
 }
 
@@ -53,7 +94,10 @@ impl Serialize for MessageContent {
     {
         match self {
             MessageContent::Local(v) => {
-                MessageContent::Remote(v.to_bincode()).serialize(serializer)
+                let content = v
+                    .encode(Codec::current())
+                    .map_err(|e| S::Error::custom(format!("{:?}", e)))?;
+                serializer.serialize_bytes(&content)
             }
             MessageContent::Remote(content) => serializer.serialize_bytes(content),
         }
@@ -71,25 +115,71 @@ impl<'de> Deserialize<'de> for MessageContent {
     }
 }
 
+impl Clone for MessageContent {
+    fn clone(&self) -> MessageContent {
+        match self {
+            MessageContent::Local(v) => MessageContent::Local(v.clone()),
+            MessageContent::Remote(content) => MessageContent::Remote(content.clone()),
+        }
+    }
+}
+
+/// Identifies where the reply to a message sent with [`crate::actors::ActorId::ask`] goes.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ReplyTo {
+    /// The UUID of the actor system waiting for the reply.
+    pub(crate) system_uuid: Uuid,
+    /// The id the actor system uses to correlate the reply with the request.
+    pub(crate) request_id: Uuid,
+}
+
 /// Holds the data used in a message.
 #[derive(Serialize, Deserialize)]
 struct MessageData {
-    /// The hash of the [`TypeId`] for the type used to construct the message.
-    type_id_hash: u64,
+    /// The hash of the name of the type used to construct the message, which identifies the
+    /// type on every actor system. See [`Message::register_type`].
+    type_hash: u64,
     /// The content of the message in a RwLock. The lock is needed because if the message
     /// came from remote, it will need to be converted to a local message variant.
     content: RwLock<MessageContent>,
+    /// Where to send the reply if the message is a request sent with
+    /// [`crate::actors::ActorId::ask`].
+    reply_to: Option<ReplyTo>,
 }
 
 /// A type for a message sent to an actor channel.
 ///
 /// Note that this type uses an internal [`Arc`] so there is no reason to surround it with
 /// another [`Arc`] to make it thread safe.
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Deserialize)]
 pub struct Message {
     data: Arc<MessageData>,
 }
 
+impl Serialize for Message {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        // Local content of a type that doesn't have the name it was sent under would be
+        // decoded by the receiver as the type that does, so it isn't encoded at all.
+        let owner = match &*self.data.content.read().unwrap() {
+            MessageContent::Local(content) => Some((**content).type_id()),
+            MessageContent::Remote(_) => None,
+        };
+        if let Some(type_id) = owner {
+            if !Message::owns_type_hash(self.data.type_hash, type_id) {
+                return Err(S::Error::custom(
+                    "The type of the message content doesn't have the name it is sent under",
+                ));
+            }
+        }
+        let mut state = serializer.serialize_struct("Message", 1)?;
+        state.serialize_field("data", &self.data)?;
+        state.end()
+    }
+}
+
 impl Message {
     /// Creates a new message from a value, transferring ownership to the message.
     ///
@@ -105,8 +195,9 @@ impl Message {
     {
         Message {
             data: Arc::new(MessageData {
-                type_id_hash: Message::hash_type_id::<T>(),
+                type_hash: Message::type_hash::<T>(),
                 content: RwLock::new(MessageContent::Local(Arc::new(value))),
+                reply_to: None,
             }),
         }
     }
@@ -129,24 +220,155 @@ impl Message {
     {
         Message {
             data: Arc::new(MessageData {
-                type_id_hash: Message::hash_type_id::<T>(),
+                type_hash: Message::type_hash::<T>(),
                 content: RwLock::new(MessageContent::Local(value.clone())),
+                reply_to: None,
+            }),
+        }
+    }
+
+    /// Creates a copy of this message that is a request whose reply goes to `reply_to`. The
+    /// content itself is shared with this message.
+    pub(crate) fn with_reply_to(&self, reply_to: ReplyTo) -> Message {
+        Message {
+            data: Arc::new(MessageData {
+                type_hash: self.data.type_hash,
+                content: RwLock::new(self.data.content.read().unwrap().clone()),
+                reply_to: Some(reply_to),
             }),
         }
     }
 
-    /// A helper that will return the hash of the type id for `T`.
-    #[inline]
-    fn hash_type_id<T: 'static>() -> u64 {
-        let mut hasher = DefaultHasher::new();
-        TypeId::of::<T>().hash(&mut hasher);
-        hasher.finish()
+    /// Checks whether this message and the `other` message are the same message, meaning one
+    /// is a clone of the other, rather than just having equal content.
+    pub(crate) fn ptr_eq(&self, other: &Message) -> bool {
+        Arc::ptr_eq(&self.data, &other.data)
+    }
+
+    /// Checks whether the message is a request sent with [`crate::actors::ActorId::ask`] and
+    /// thus expects a reply.
+    pub fn is_request(&self) -> bool {
+        self.data.reply_to.is_some()
+    }
+
+    /// Replies to this message with the `reply` message. This works regardless of whether the
+    /// request came from the local actor system or a remote one. If the message was not sent
+    /// with [`crate::actors::ActorId::ask`] an [`ActorError::ReplyNotExpected`] is returned.
+    /// Note that this uses the actor system of the calling thread to send the reply.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use axiom::*;
+    /// use std::time::Duration;
+    ///
+    /// let system = ActorSystem::create(ActorSystemConfig::default());
+    /// system.init_current();
+    ///
+    /// let aid = system.spawn(0, |_: &mut usize, _: ActorId, message: &Message| {
+    ///     if let Some(value) = message.content_as::<i32>() {
+    ///         message.reply(Message::new(*value * 2)).unwrap();
+    ///     }
+    ///     Status::Processed
+    /// });
+    ///
+    /// let reply = aid.ask(Message::new(11), Duration::from_secs(1)).wait().unwrap();
+    /// assert_eq!(22, *reply.content_as::<i32>().unwrap());
+    /// ```
+    pub fn reply(&self, reply: Message) -> Result<(), ActorError> {
+        match self.data.reply_to {
+            Some(reply_to) => ActorSystem::current().send_reply(reply_to, reply),
+            None => Err(ActorError::ReplyNotExpected),
+        }
+    }
+
+    /// Registers the `name` that identifies messages of type `T` between actor systems.
+    ///
+    /// A type that isn't registered is identified by the name [`std::any::type_name`] gives
+    /// it, which includes the module path of the type. That name can change with the version of
+    /// the compiler, so actor systems built differently should register a name for each type
+    /// they exchange. Every actor system has to register the same name for the type and do so
+    /// before any message of the type is created. If another type already has the name, or a
+    /// name with the same hash, an [`ActorError::TypeNameAlreadyUsed`] is returned.
+    ///
+    /// # Examples
+    /// ```rust
+    /// use axiom::*;
+    /// use serde::{Deserialize, Serialize};
+    ///
+    /// #[derive(Serialize, Deserialize)]
+    /// struct Request {
+    ///     id: u64,
+    /// }
+    ///
+    /// Message::register_type::<Request>("example.Request").unwrap();
+    /// let msg = Message::new(Request { id: 11 });
+    /// ```
+    pub fn register_type<T: ActorMessage>(name: &str) -> Result<(), ActorError> {
+        let type_id = TypeId::of::<T>();
+        let hash = fnv1a(name);
+        let mut type_names = TYPE_NAMES.write().unwrap();
+        match type_names.types.get(&hash) {
+            Some(owner) if *owner != type_id => {
+                Err(ActorError::TypeNameAlreadyUsed(name.to_string()))
+            }
+            _ => {
+                // The type may not have had the old name if another type had it first.
+                if let Some(old_hash) = type_names.hashes.insert(type_id, hash) {
+                    if type_names.types.get(&old_hash) == Some(&type_id) {
+                        type_names.types.remove(&old_hash);
+                    }
+                }
+                type_names.types.insert(hash, type_id);
+                Ok(())
+            }
+        }
+    }
+
+    /// A helper that will return the hash of the name of `T`, naming the type after
+    /// [`std::any::type_name`] if it hasn't been named yet. If another type already has a name
+    /// with the same hash, that type keeps it and messages with the hash are never encoded or
+    /// decoded as a `T`.
+    fn type_hash<T: 'static>() -> u64 {
+        let type_id = TypeId::of::<T>();
+        if let Some(hash) = TYPE_NAMES.read().unwrap().hashes.get(&type_id) {
+            return *hash;
+        }
+        let name = any::type_name::<T>();
+        let hash = fnv1a(name);
+        let mut type_names = TYPE_NAMES.write().unwrap();
+        // Another thread could have named the type since the read lock was dropped.
+        if let Some(hash) = type_names.hashes.get(&type_id) {
+            return *hash;
+        }
+        if *type_names.types.entry(hash).or_insert(type_id) != type_id {
+            error!(
+                "The name of the message type {} has the same hash as another type so its \
+                 messages can't be received from remote; register another name for it",
+                name
+            );
+        }
+        type_names.hashes.insert(type_id, hash);
+        hash
+    }
+
+    /// Checks whether the type with the given id is the type that has the name with the given
+    /// `hash`.
+    fn owns_type_hash(hash: u64, type_id: TypeId) -> bool {
+        TYPE_NAMES.read().unwrap().types.get(&hash) == Some(&type_id)
+    }
+
+    /// Checks whether the content of the message is of type `T` without converting it.
+    pub(crate) fn is_type<T: 'static>(&self) -> bool {
+        self.data.type_hash == Message::type_hash::<T>()
     }
 
     /// Get the content as an [`Arc<T>`]. If this fails a `None` will be returned.  Note that
     /// the user need not worry whether the message came from a local or remote source as the
     /// heavy lifting for that is done internally. The first successful attempt to downcast a
-    /// remote message will result in the value being converted to a local message.
+    /// remote message will result in the value being converted to a local message, decoding it
+    /// with the [`Codec`] of the actor system of the current thread. Remote content that has
+    /// the name of `T` but can't be decoded as one, because the actor systems disagree on what
+    /// the type is, is logged and `None` is returned.
     ///
     /// # Examples
     /// ```rust
@@ -158,13 +380,14 @@ impl Message {
     /// assert_eq!(value, *msg.content_as::<i32>().unwrap());
     /// assert_eq!(None, msg.content_as::<u32>());
     /// ```
+    #[allow(clippy::needless_maybe_sized)]
     pub fn content_as<T>(&self) -> Option<Arc<T>>
     where
         T: 'static + ActorMessage + DeserializeOwned + ?Sized,
     {
-        // To make this fail fast we will first check against the hash of the type_id that the
-        // user wants to convert the message content to.
-        if self.data.type_id_hash != Message::hash_type_id::<T>() {
+        // To make this fail fast we will first check against the hash of the name of the type
+        // that the user wants to convert the message content to.
+        if self.data.type_hash != Message::type_hash::<T>() {
             None
         } else {
             // We first have to figure out if the content is Local or Remote because they have
@@ -179,6 +402,11 @@ impl Message {
                     // To convert the message we have to drop the read lock and re-acquire a
                     // write lock on the content.
                     drop(read_guard);
+                    // If the name of `T` collides with that of another type, the content is
+                    // that type's as far as this actor system can tell.
+                    if !Message::owns_type_hash(self.data.type_hash, TypeId::of::<T>()) {
+                        return None;
+                    }
                     let mut write_guard = self.data.content.write().unwrap();
                     // Because of a potential race we will try again.
                     match &*write_guard {
@@ -187,7 +415,7 @@ impl Message {
                         // This thread got the write lock and the content is still remote.
                         MessageContent::Remote(content) => {
                             // We deserialize the content and replace it in the message.
-                            match bincode::deserialize::<T>(&content) {
+                            match Codec::current().decode::<T>(content) {
                                 Ok(concrete) => {
                                     // with a new local variant.
                                     let new_value: Arc<T> = Arc::new(concrete);
@@ -196,9 +424,14 @@ impl Message {
                                     Some(new_value.clone())
                                 }
                                 Err(err) => {
-                                    // The only reason this should happen is if the type id hash
-                                    // check is somehow broken so we will want to fix it.
-                                    panic!("Deserialization shouldn't have failed: {:?}", err)
+                                    // The sender has another type by the same name so we leave
+                                    // the content as it is.
+                                    warn!(
+                                        "Unable to decode a message as {}: {:?}",
+                                        any::type_name::<T>(),
+                                        err
+                                    );
+                                    None
                                 }
                             }
                         }
@@ -210,6 +443,7 @@ impl Message {
 }
 
 #[cfg(test)]
+#[allow(clippy::assertions_on_constants, clippy::unnecessary_cast)]
 mod tests {
     use super::*;
 
@@ -294,12 +528,12 @@ mod tests {
         let local = Message::new(value);
         let serialized = bincode::serialize(&local).expect("Couldn't serialize.");
         let msg: Message = bincode::deserialize(&serialized).expect("Couldn't deserialize.");
-        let hash = Message::hash_type_id::<i32>();
+        let hash = Message::type_hash::<i32>();
         {
             // A failure to downcast should leave the message as it is.
             assert_eq!(None, msg.content_as::<u32>());
             let read_guard = msg.data.content.read().unwrap();
-            assert_eq!(hash, msg.data.type_id_hash);
+            assert_eq!(hash, msg.data.type_hash);
             match &*read_guard {
                 MessageContent::Local(_) => assert!(false, "Expected a Remote variant."),
                 MessageContent::Remote(content) => {
@@ -315,7 +549,7 @@ mod tests {
 
             // Now we test to make sure that it indeed got converted.
             let read_guard = msg.data.content.read().unwrap();
-            assert_eq!(hash, msg.data.type_id_hash);
+            assert_eq!(hash, msg.data.type_hash);
             match &*read_guard {
                 MessageContent::Remote(_) => assert!(false, "Expected a Local variant."),
                 MessageContent::Local(content) => {
@@ -324,4 +558,99 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_type_hash() {
+        // The hash is FNV-1a which gives known values.
+        assert_eq!(0xcbf2_9ce4_8422_2325, fnv1a(""));
+        assert_eq!(0xaf63_dc4c_8601_ec8c, fnv1a("a"));
+
+        // Types that aren't registered are named by their type name.
+        assert_eq!(fnv1a("i32"), Message::new(11_i32).data.type_hash);
+
+        // The message types of Axiom have fixed names.
+        assert_eq!(fnv1a("axiom.SystemMsg"), Message::type_hash::<SystemMsg>());
+        assert_eq!(fnv1a("axiom.ActorId"), Message::type_hash::<ActorId>());
+    }
+
+    #[test]
+    fn test_register_type() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Registered(i32);
+        #[derive(Serialize, Deserialize)]
+        struct Other(i32);
+
+        Message::register_type::<Registered>("test.Registered").unwrap();
+        let msg = Message::new(Registered(11));
+        assert_eq!(fnv1a("test.Registered"), msg.data.type_hash);
+
+        // Registering the name again for the same type is fine but not for another type.
+        Message::register_type::<Registered>("test.Registered").unwrap();
+        assert_eq!(
+            Err(ActorError::TypeNameAlreadyUsed(
+                "test.Registered".to_string()
+            )),
+            Message::register_type::<Other>("test.Registered")
+        );
+        let _ = Message::new(0_i32);
+        assert_eq!(
+            Err(ActorError::TypeNameAlreadyUsed("i32".to_string())),
+            Message::register_type::<Other>("i32")
+        );
+
+        // The name is what identifies the type of remote content.
+        let serialized = bincode::serialize(&msg).expect("Couldn't serialize.");
+        let remote: Message = bincode::deserialize(&serialized).expect("Couldn't deserialize.");
+        assert!(remote.content_as::<Other>().is_none());
+        assert_eq!(Registered(11), *remote.content_as::<Registered>().unwrap());
+    }
+
+    #[test]
+    fn test_type_name_collision() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Late(i32);
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Squatter(i32);
+
+        // A type registered under the name another type would take keeps the name.
+        Message::register_type::<Squatter>(any::type_name::<Late>()).unwrap();
+        let msg = Message::new(Late(11));
+        assert_eq!(Late(11), *msg.content_as::<Late>().unwrap());
+
+        // Content of the type that doesn't have the name isn't sent under it.
+        let error = Codec::Bincode.encode(&msg).unwrap_err();
+        assert!(matches!(error, ActorError::Serialization(_)));
+
+        // Remote content with the name is only ever decoded as the type that has it.
+        let serialized = bincode::serialize(&Message::new(Squatter(11))).unwrap();
+        let remote: Message = bincode::deserialize(&serialized).expect("Couldn't deserialize.");
+        assert_eq!(None, remote.content_as::<Late>());
+        assert_eq!(Squatter(11), *remote.content_as::<Squatter>().unwrap());
+
+        // Naming the late type doesn't take the name from the type that has it.
+        Message::register_type::<Late>("test.Late").unwrap();
+        let hash = fnv1a(any::type_name::<Late>());
+        assert!(Message::owns_type_hash(hash, TypeId::of::<Squatter>()));
+        let serialized = bincode::serialize(&Message::new(Late(11))).unwrap();
+        let remote: Message = bincode::deserialize(&serialized).expect("Couldn't deserialize.");
+        assert_eq!(Late(11), *remote.content_as::<Late>().unwrap());
+    }
+
+    #[test]
+    fn test_undecodable_content() {
+        // Remote content with the name of a type that it isn't doesn't convert.
+        let msg = Message {
+            data: Arc::new(MessageData {
+                type_hash: Message::type_hash::<String>(),
+                content: RwLock::new(MessageContent::Remote(vec![1])),
+                reply_to: None,
+            }),
+        };
+        assert_eq!(None, msg.content_as::<String>());
+        let read_guard = msg.data.content.read().unwrap();
+        match &*read_guard {
+            MessageContent::Local(_) => panic!("Expected a Remote variant."),
+            MessageContent::Remote(content) => assert_eq!(vec![1], *content),
+        }
+    }
 }
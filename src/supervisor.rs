@@ -0,0 +1,395 @@
+//! Implements supervisors which are actors that start, monitor and restart other actors.
+//!
+//! A supervisor is given a list of factories, each of which spawns one child actor. When the
+//! supervisor starts it spawns its children in order and monitors them. If a child stops
+//! abnormally, as decided by [`StopReason::is_abnormal`], the supervisor restarts it and,
+//! depending on the
+//! [`RestartStrategy`], some of its siblings. To keep a child that keeps failing from being
+//! restarted forever, the supervisor allows at most [`SupervisorConfig::max_restarts`]
+//! restarts within [`SupervisorConfig::restart_window`] milliseconds. When that limit is
+//! exceeded the supervisor stops all of its children and then stops itself, which escalates
+//! the failure to whoever is supervising the supervisor.
+//!
+//! Supervisors can be nested simply by having the factory of a child spawn another supervisor.
+
+use crate::actors::*;
+use crate::message::*;
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::time::{Duration, Instant};
+
+/// The strategy a supervisor uses to decide which children to restart when a child stops.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum RestartStrategy {
+    /// Only the child that stopped is restarted.
+    OneForOne,
+
+    /// All of the children are stopped and restarted when one of them stops. This is useful
+    /// when the children depend so much on each other that they can't work without each other.
+    OneForAll,
+
+    /// The child that stopped and all of the children that were started after it are stopped
+    /// and restarted. This is useful when later children depend on earlier ones.
+    RestForOne,
+}
+
+/// Configuration for a supervisor. Note that like the [`ActorSystemConfig`] this configuration
+/// implements serde serialize and deserialize so it can be read from a config file.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SupervisorConfig {
+    /// The strategy used to restart the children.
+    pub strategy: RestartStrategy,
+    /// The maximum number of restarts allowed within the `restart_window`. If the children
+    /// are restarted more often than this the supervisor gives up and stops.
+    pub max_restarts: u16,
+    /// The length of the window in milliseconds in which restarts are counted.
+    pub restart_window: u32,
+}
+
+impl Default for SupervisorConfig {
+    /// Creates a config for a one for one supervisor allowing three restarts in five seconds.
+    fn default() -> SupervisorConfig {
+        SupervisorConfig {
+            strategy: RestartStrategy::OneForOne,
+            max_restarts: 3,
+            restart_window: 5000,
+        }
+    }
+}
+
+/// A type for a function that spawns a child of a supervisor on the given actor system and
+/// returns the [`ActorId`] of the child. The function is called every time the child is
+/// started or restarted.
+pub trait ChildFactory: Fn(&ActorSystem) -> ActorId + Send + Sync {}
+
+// Allows any function, static or closure, to be used as a child factory.
+impl<F> ChildFactory for F where F: Fn(&ActorSystem) -> ActorId + Send + Sync + 'static {}
+
+/// The state of a supervisor actor.
+struct Supervisor {
+    /// The configuration of the supervisor.
+    config: SupervisorConfig,
+    /// The factories of the children in the order the children are started.
+    factories: Vec<Box<dyn ChildFactory>>,
+    /// The currently running children, in the same order as the factories.
+    children: Vec<ActorId>,
+    /// Children that were stopped by the supervisor itself so their stopped message is
+    /// expected and should not cause a restart.
+    stopping: HashSet<ActorId>,
+    /// The times of the restarts within the restart window.
+    restarts: VecDeque<Instant>,
+}
+
+impl Supervisor {
+    /// Spawns the child at the given `index` using its factory and starts monitoring it.
+    fn start_child(&mut self, system: &ActorSystem, aid: &ActorId, index: usize) -> ActorId {
+        let child = (self.factories[index])(system);
+        system.monitor(aid, &child);
+        child
+    }
+
+    /// Stops the children from `from` to the end in reverse order of how they were started.
+    /// Children that have already stopped are skipped.
+    fn stop_children(&mut self, system: &ActorSystem, from: usize) {
+        for child in self.children[from..].iter().rev() {
+            if system.is_alive(child) {
+                self.stopping.insert(child.clone());
+                system.stop(child.clone());
+            }
+        }
+    }
+
+    /// Restarts the children from `from` to the end in the order they were originally started.
+    fn restart_children(&mut self, system: &ActorSystem, aid: &ActorId, from: usize) {
+        for index in from..self.children.len() {
+            self.children[index] = self.start_child(system, aid, index);
+        }
+    }
+
+    /// Records a restart and checks whether the supervisor is still within its restart
+    /// intensity.
+    fn record_restart(&mut self) -> bool {
+        let now = Instant::now();
+        let window = Duration::from_millis(self.config.restart_window as u64);
+        while let Some(oldest) = self.restarts.front() {
+            if now.duration_since(*oldest) > window {
+                self.restarts.pop_front();
+            } else {
+                break;
+            }
+        }
+        self.restarts.push_back(now);
+        self.restarts.len() <= self.config.max_restarts as usize
+    }
+
+    /// Handles the child with the given `stopped` aid stopping on its own for the `reason`.
+    fn child_stopped(&mut self, aid: ActorId, stopped: &ActorId, reason: &StopReason) -> Status {
+        let system = ActorSystem::current();
+        let index = match self.children.iter().position(|c| c == stopped) {
+            Some(index) => index,
+            None => return Status::Processed,
+        };
+        if !reason.is_abnormal() {
+            return Status::Processed;
+        }
+
+        if !self.record_restart() {
+            error!(
+                "Supervisor {:?} exceeded {} restarts in {} ms, stopping.",
+                aid, self.config.max_restarts, self.config.restart_window
+            );
+            self.stop_children(&system, 0);
+            // Stopping with a forced reason rather than returning `Status::Stop` lets the
+            // supervisor of this supervisor know that this was not a normal stop.
+            system.stop(aid);
+            return Status::Processed;
+        }
+
+        warn!(
+            "Supervisor {:?} restarting child {:?} that stopped: {:?}",
+            aid, stopped, reason
+        );
+        match self.config.strategy {
+            RestartStrategy::OneForOne => {
+                self.children[index] = self.start_child(&system, &aid, index);
+            }
+            RestartStrategy::OneForAll => {
+                self.stop_children(&system, 0);
+                self.restart_children(&system, &aid, 0);
+            }
+            RestartStrategy::RestForOne => {
+                self.stop_children(&system, index);
+                self.restart_children(&system, &aid, index);
+            }
+        }
+        Status::Processed
+    }
+
+    /// The message processor of the supervisor actor.
+    fn handle(&mut self, aid: ActorId, message: &Message) -> Status {
+        if let Some(msg) = message.content_as::<SystemMsg>() {
+            match &*msg {
+                SystemMsg::Start => {
+                    let system = ActorSystem::current();
+                    for index in 0..self.factories.len() {
+                        let child = self.start_child(&system, &aid, index);
+                        self.children.push(child);
+                    }
+                    Status::Processed
+                }
+                SystemMsg::Stop => {
+                    self.stop_children(&ActorSystem::current(), 0);
+                    Status::Stop
+                }
+                SystemMsg::Stopped {
+                    aid: stopped,
+                    reason,
+                } => {
+                    if self.stopping.remove(stopped) {
+                        Status::Processed
+                    } else {
+                        self.child_stopped(aid, stopped, reason)
+                    }
+                }
+                _ => Status::Processed,
+            }
+        } else {
+            Status::Processed
+        }
+    }
+}
+
+impl ActorSystem {
+    /// Spawns a supervisor actor that starts a child from each of the given `factories`, in
+    /// order, and restarts them according to the `config` when they stop. The returned
+    /// [`ActorId`] is that of the supervisor. Sending the supervisor a [`SystemMsg::Stop`]
+    /// stops all of its children as well.
+    ///
+    /// # Examples
+    /// ```
+    /// use axiom::*;
+    ///
+    /// let system = ActorSystem::create(ActorSystemConfig::default());
+    /// system.init_current();
+    ///
+    /// let factory = |system: &ActorSystem| {
+    ///     system.spawn(0, |_: &mut usize, _: ActorId, _: &Message| Status::Processed)
+    /// };
+    /// let supervisor = system.spawn_supervisor(SupervisorConfig::default(), vec![Box::new(factory)]);
+    /// ```
+    pub fn spawn_supervisor(
+        &self,
+        config: SupervisorConfig,
+        factories: Vec<Box<dyn ChildFactory>>,
+    ) -> ActorId {
+        let supervisor = Supervisor {
+            config,
+            factories,
+            children: Vec::new(),
+            stopping: HashSet::new(),
+            restarts: VecDeque::new(),
+        };
+        self.spawn(supervisor, Supervisor::handle)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+
+    /// A child that panics when it is sent any `i32` and stops normally when sent a `bool`.
+    fn crashing_child(name: &'static str) -> Box<dyn ChildFactory> {
+        Box::new(move |system: &ActorSystem| spawn_named_crashing(system, name))
+    }
+
+    /// Waits for the child with the given `name` to be restarted, which means there is a
+    /// child with that name which is not `old`.
+    fn await_restart(system: &ActorSystem, name: &str, old: &ActorId) -> ActorId {
+        assert_await(
+            || match system.find_aid_by_name(name) {
+                Some(aid) => aid != *old,
+                None => false,
+            },
+            1000,
+        );
+        system.find_aid_by_name(name).unwrap()
+    }
+
+    /// Spawns a supervisor with three children named `a`, `b` and `c` and returns the
+    /// supervisor along with the children.
+    fn spawn_abc(system: &ActorSystem, config: SupervisorConfig) -> (ActorId, Vec<ActorId>) {
+        let factories = vec![
+            crashing_child("a"),
+            crashing_child("b"),
+            crashing_child("c"),
+        ];
+        let supervisor = system.spawn_supervisor(config, factories);
+        let children = ["a", "b", "c"]
+            .iter()
+            .map(|name| {
+                assert_await(|| system.find_aid_by_name(name).is_some(), 1000);
+                system.find_aid_by_name(name).unwrap()
+            })
+            .collect();
+        (supervisor, children)
+    }
+
+    #[test]
+    fn test_one_for_one() {
+        init_test_log();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+        let (_supervisor, children) = spawn_abc(&system, SupervisorConfig::default());
+
+        // Only the child that stopped is restarted.
+        children[1].send(Message::new(11));
+        let b = await_restart(&system, "b", &children[1]);
+        assert_ne!(children[1], b);
+        assert_eq!(Some(children[0].clone()), system.find_aid_by_name("a"));
+        assert_eq!(Some(children[2].clone()), system.find_aid_by_name("c"));
+
+        // A child that stops normally is not restarted.
+        children[0].send(Message::new(true));
+        assert_await(|| system.find_aid_by_name("a").is_none(), 1000);
+        b.send(Message::new(11));
+        await_restart(&system, "b", &b);
+        assert_eq!(None, system.find_aid_by_name("a"));
+
+        system.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_one_for_all() {
+        init_test_log();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+        let config = SupervisorConfig {
+            strategy: RestartStrategy::OneForAll,
+            ..SupervisorConfig::default()
+        };
+        let (_supervisor, children) = spawn_abc(&system, config);
+
+        // Every child is restarted.
+        children[1].send(Message::new(11));
+        await_restart(&system, "a", &children[0]);
+        await_restart(&system, "b", &children[1]);
+        await_restart(&system, "c", &children[2]);
+        assert!(children.iter().all(|c| !system.is_alive(c)));
+
+        system.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_rest_for_one() {
+        init_test_log();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+        let config = SupervisorConfig {
+            strategy: RestartStrategy::RestForOne,
+            ..SupervisorConfig::default()
+        };
+        let (_supervisor, children) = spawn_abc(&system, config);
+
+        // The child that stopped and the ones started after it are restarted.
+        children[1].send(Message::new(11));
+        await_restart(&system, "b", &children[1]);
+        await_restart(&system, "c", &children[2]);
+        assert_eq!(Some(children[0].clone()), system.find_aid_by_name("a"));
+
+        system.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_restart_intensity() {
+        init_test_log();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+        let config = SupervisorConfig {
+            max_restarts: 1,
+            ..SupervisorConfig::default()
+        };
+        let (supervisor, children) = spawn_abc(&system, config);
+
+        // A parent monitoring the supervisor should be told when the supervisor gives up.
+        let parent = system.spawn(0, |_: &mut usize, _: ActorId, _: &Message| {
+            Status::Processed
+        });
+        system.monitor(&parent, &supervisor);
+
+        children[0].send(Message::new(11));
+        let a = await_restart(&system, "a", &children[0]);
+        a.send(Message::new(11));
+        assert_await(|| !system.is_alive(&supervisor), 1000);
+        assert_await_received(&parent, 2, 1000);
+        assert_await(|| system.find_aid_by_name("b").is_none(), 1000);
+        assert_await(|| system.find_aid_by_name("c").is_none(), 1000);
+
+        system.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_nested_supervisors() {
+        init_test_log();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+
+        // The inner supervisor gives up on the first restart and the outer one restarts it.
+        let inner = |system: &ActorSystem| {
+            let config = SupervisorConfig {
+                max_restarts: 0,
+                ..SupervisorConfig::default()
+            };
+            system.spawn_supervisor(config, vec![crashing_child("leaf")])
+        };
+        system.spawn_supervisor(SupervisorConfig::default(), vec![Box::new(inner)]);
+        assert_await(|| system.find_aid_by_name("leaf").is_some(), 1000);
+        let leaf = system.find_aid_by_name("leaf").unwrap();
+
+        leaf.send(Message::new(11));
+        await_restart(&system, "leaf", &leaf);
+
+        system.trigger_and_await_shutdown();
+    }
+}
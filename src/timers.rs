@@ -0,0 +1,668 @@
+//! Implements timers that deliver messages to actors after a delay or periodically.
+//!
+//! Every actor system owns a timer thread that keeps the pending timers ordered by the time
+//! they are due and sends the messages when that time comes. Timers are created with
+//! [`ActorSystem::send_after`] and [`ActorSystem::send_interval`] which return a
+//! [`TimerHandle`] that can be used to cancel the timer. The timers of an actor are cancelled
+//! automatically when the actor stops.
+//!
+//! The timers read the time from a [`Clock`]. Normally this is the [`SystemClock`] but tests
+//! can create the actor system with a [`VirtualClock`] using [`ActorSystem::create_with_clock`]
+//! and move time forward by hand to test timing dependent behavior without waiting.
+
+use crate::actors::*;
+use crate::message::*;
+use log::warn;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// A source of the current time for the timers of an actor system.
+pub trait Clock: Send + Sync {
+    /// Returns the current time.
+    fn now(&self) -> Instant;
+
+    /// Registers a function that the clock calls when its time jumps forward other than by
+    /// the passing of real time, so that the timers can check whether any of them are due.
+    /// Clocks that follow real time don't need to do anything.
+    fn on_advance(&self, _wake: Box<dyn Fn() + Send + Sync>) {}
+}
+
+/// A clock that follows the real time of the system.
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves forward when told to, which is useful for testing.
+///
+/// # Examples
+/// ```
+/// use axiom::*;
+/// use std::sync::Arc;
+/// use std::time::Duration;
+///
+/// let clock = Arc::new(VirtualClock::new());
+/// let system = ActorSystem::create_with_clock(ActorSystemConfig::default(), clock.clone());
+/// system.init_current();
+///
+/// let aid = system.spawn(0, |_: &mut usize, _: ActorId, _: &Message| Status::Processed);
+/// let timer = system.send_after(Message::new(11), aid, Duration::from_secs(60));
+/// assert!(timer.is_active());
+///
+/// // Moving the clock forward makes the timer fire right away.
+/// clock.advance(Duration::from_secs(60));
+/// ```
+pub struct VirtualClock {
+    /// The time when the clock was created.
+    start: Instant,
+    /// How far the clock has been advanced since it was created.
+    elapsed: Mutex<Duration>,
+    /// The functions to call when the clock is advanced.
+    listeners: Mutex<Vec<Box<dyn Fn() + Send + Sync>>>,
+}
+
+impl VirtualClock {
+    /// Creates a new virtual clock.
+    pub fn new() -> VirtualClock {
+        VirtualClock {
+            start: Instant::now(),
+            elapsed: Mutex::new(Duration::from_millis(0)),
+            listeners: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Moves the clock forward by the given `duration`.
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap() += duration;
+        for wake in self.listeners.lock().unwrap().iter() {
+            wake();
+        }
+    }
+}
+
+impl Default for VirtualClock {
+    fn default() -> VirtualClock {
+        VirtualClock::new()
+    }
+}
+
+impl Clock for VirtualClock {
+    fn now(&self) -> Instant {
+        self.start + *self.elapsed.lock().unwrap()
+    }
+
+    fn on_advance(&self, wake: Box<dyn Fn() + Send + Sync>) {
+        self.listeners.lock().unwrap().push(wake);
+    }
+}
+
+impl fmt::Debug for VirtualClock {
+    fn fmt(&self, formatter: &'_ mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "VirtualClock{{elapsed: {:?}}}",
+            *self.elapsed.lock().unwrap()
+        )
+    }
+}
+
+/// A pending timer.
+struct Timer {
+    /// The actor the message is sent to.
+    aid: ActorId,
+    /// The message to send.
+    message: Message,
+    /// The interval for a periodic timer or `None` if the timer fires only once.
+    interval: Option<Duration>,
+    /// The activity that postpones an idle timer. See [`Timers::add_idle`].
+    idle: Option<Arc<Idle>>,
+}
+
+/// Tracks the last activity of an actor for a timer that fires only once the actor has been
+/// idle for a while. Recording activity doesn't touch the timer queue; the timer checks the
+/// last activity when it comes due and postpones itself if the actor was active since.
+pub(crate) struct Idle {
+    /// How long the actor has to be idle for the timer to fire.
+    duration: Duration,
+    /// The last time the actor was active.
+    last_activity: Mutex<Instant>,
+}
+
+impl Idle {
+    /// Records that the actor was active at the time `now`.
+    pub(crate) fn touch(&self, now: Instant) {
+        *self.last_activity.lock().unwrap() = now;
+    }
+
+    /// The time the actor will have been idle for the duration if it stays idle.
+    pub(crate) fn deadline(&self) -> Instant {
+        *self.last_activity.lock().unwrap() + self.duration
+    }
+}
+
+/// The pending timers of an actor system.
+#[derive(Default)]
+struct TimerQueue {
+    /// The id to give the next timer.
+    next_id: u64,
+    /// The pending timers keyed by their id.
+    timers: HashMap<u64, Timer>,
+    /// The functions of the pending timers added with [`Timers::add_call`] keyed by their id.
+    calls: HashMap<u64, Call>,
+    /// The ids of the timers ordered by the time they are due. Cancelled timers are only
+    /// removed from `timers` so ids that are no longer there are skipped.
+    due: BinaryHeap<Reverse<(Instant, u64)>>,
+}
+
+/// The timer service of an actor system.
+pub(crate) struct Timers {
+    /// The clock the timers read the time from.
+    clock: Arc<dyn Clock>,
+    /// The pending timers.
+    queue: Mutex<TimerQueue>,
+    /// Signalled when the timers change so that the timer thread recomputes how long to wait.
+    condvar: Condvar,
+}
+
+impl Timers {
+    /// Creates the timer service using the given `clock`.
+    pub(crate) fn new(clock: Arc<dyn Clock>) -> Arc<Timers> {
+        let timers = Arc::new(Timers {
+            clock: clock.clone(),
+            queue: Mutex::new(TimerQueue::default()),
+            condvar: Condvar::new(),
+        });
+        let weak = Arc::downgrade(&timers);
+        clock.on_advance(Box::new(move || {
+            if let Some(timers) = weak.upgrade() {
+                let _guard = timers.queue.lock().unwrap();
+                timers.condvar.notify_all();
+            }
+        }));
+        timers
+    }
+
+    /// Adds a timer that sends the `message` to the `aid` after the `delay` and then every
+    /// `interval` if one is given.
+    pub(crate) fn add(
+        self: &Arc<Self>,
+        aid: ActorId,
+        message: Message,
+        delay: Duration,
+        interval: Option<Duration>,
+    ) -> TimerHandle {
+        let mut queue = self.queue.lock().unwrap();
+        let id = queue.next_id;
+        queue.next_id += 1;
+        queue.timers.insert(
+            id,
+            Timer {
+                aid,
+                message,
+                interval,
+                idle: None,
+            },
+        );
+        queue.due.push(Reverse((self.clock.now() + delay, id)));
+        self.condvar.notify_all();
+        TimerHandle {
+            id,
+            timers: self.clone(),
+        }
+    }
+
+    /// Adds a timer that sends the `message` to the `aid` whenever the actor has been idle for
+    /// the `duration`, as recorded with the returned [`Idle`], until the timer is cancelled.
+    pub(crate) fn add_idle(
+        self: &Arc<Self>,
+        aid: ActorId,
+        message: Message,
+        duration: Duration,
+    ) -> (TimerHandle, Arc<Idle>) {
+        let idle = Arc::new(Idle {
+            duration,
+            last_activity: Mutex::new(self.clock.now()),
+        });
+        let mut queue = self.queue.lock().unwrap();
+        let id = queue.next_id;
+        queue.next_id += 1;
+        queue.timers.insert(
+            id,
+            Timer {
+                aid,
+                message,
+                interval: None,
+                idle: Some(idle.clone()),
+            },
+        );
+        queue.due.push(Reverse((idle.deadline(), id)));
+        self.condvar.notify_all();
+        let handle = TimerHandle {
+            id,
+            timers: self.clone(),
+        };
+        (handle, idle)
+    }
+
+    /// Adds a timer that calls the function `call` on the timer thread after the `delay`. The
+    /// function must not block as the other timers wait for it.
+    pub(crate) fn add_call(self: &Arc<Self>, call: Call, delay: Duration) -> TimerHandle {
+        let mut queue = self.queue.lock().unwrap();
+        let id = queue.next_id;
+        queue.next_id += 1;
+        queue.calls.insert(id, call);
+        queue.due.push(Reverse((self.clock.now() + delay, id)));
+        self.condvar.notify_all();
+        TimerHandle {
+            id,
+            timers: self.clone(),
+        }
+    }
+
+    /// The current time according to the clock of the timers.
+    pub(crate) fn now(&self) -> Instant {
+        self.clock.now()
+    }
+
+    /// Cancels the timer with the given `id`.
+    fn cancel(&self, id: u64) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.timers.remove(&id);
+        queue.calls.remove(&id);
+    }
+
+    /// Checks whether the timer with the given `id` is still pending.
+    fn is_active(&self, id: u64) -> bool {
+        let queue = self.queue.lock().unwrap();
+        queue.timers.contains_key(&id) || queue.calls.contains_key(&id)
+    }
+
+    /// Cancels all of the timers sending to the given `aid`.
+    pub(crate) fn cancel_all(&self, aid: &ActorId) {
+        let mut queue = self.queue.lock().unwrap();
+        if !queue.timers.is_empty() {
+            queue.timers.retain(|_, timer| timer.aid != *aid);
+        }
+    }
+
+    /// Removes the timers that are due and returns the messages to send and the functions to
+    /// call. Periodic timers are scheduled again for their next interval and idle timers for
+    /// when the actor could next have been idle long enough. An idle timer whose actor was
+    /// active since the timer was scheduled is postponed without firing.
+    fn take_due(&self, queue: &mut TimerQueue) -> (Vec<(u64, ActorId, Message)>, Vec<Call>) {
+        let now = self.clock.now();
+        let mut fired = Vec::new();
+        let mut calls = Vec::new();
+        while let Some(Reverse((deadline, id))) = queue.due.peek().cloned() {
+            if deadline > now {
+                break;
+            }
+            queue.due.pop();
+            if let Some(call) = queue.calls.remove(&id) {
+                calls.push(call);
+                continue;
+            }
+            let interval = match queue.timers.get(&id) {
+                Some(Timer {
+                    idle: Some(idle), ..
+                }) if idle.deadline() > now => {
+                    queue.due.push(Reverse((idle.deadline(), id)));
+                    continue;
+                }
+                Some(timer) => {
+                    fired.push((id, timer.aid.clone(), timer.message.clone()));
+                    timer
+                        .interval
+                        .or_else(|| timer.idle.as_ref().map(|i| i.duration))
+                }
+                None => continue,
+            };
+            match interval {
+                Some(interval) => {
+                    // If the timer thread fell behind, skip the missed intervals rather than
+                    // firing them all at once.
+                    let mut next = deadline + interval;
+                    if next <= now {
+                        next = now + interval;
+                    }
+                    queue.due.push(Reverse((next, id)));
+                }
+                None => {
+                    queue.timers.remove(&id);
+                }
+            }
+        }
+        (fired, calls)
+    }
+}
+
+/// The function of a timer added with [`Timers::add_call`].
+type Call = Box<dyn FnOnce() + Send>;
+
+/// A handle to a timer created with [`ActorSystem::send_after`] or
+/// [`ActorSystem::send_interval`] that can be used to cancel the timer.
+pub struct TimerHandle {
+    /// The id of the timer.
+    id: u64,
+    /// The timer service the timer belongs to.
+    timers: Arc<Timers>,
+}
+
+impl TimerHandle {
+    /// Cancels the timer. Nothing happens if the timer has already fired or been cancelled.
+    pub fn cancel(&self) {
+        self.timers.cancel(self.id);
+    }
+
+    /// Checks whether the timer is still pending, which is the case for a one shot timer that
+    /// hasn't fired yet or a periodic timer that hasn't been cancelled.
+    pub fn is_active(&self) -> bool {
+        self.timers.is_active(self.id)
+    }
+}
+
+impl fmt::Debug for TimerHandle {
+    fn fmt(&self, formatter: &'_ mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "TimerHandle{{id: {}}}", self.id)
+    }
+}
+
+/// Starts the thread that sends the messages of the timers of the `system` when they are due.
+/// The thread waits at most `wait_time` milliseconds at a time so that it notices shutdown.
+pub(crate) fn start_timer_thread(
+    system: ActorSystem,
+    timers: Arc<Timers>,
+    wait_time: u16,
+) -> JoinHandle<()> {
+    thread::spawn(move || {
+        system.init_current();
+        let max_wait = Duration::from_millis(wait_time as u64);
+        let mut queue = timers.queue.lock().unwrap();
+        while !system.is_shutdown_triggered() {
+            let (fired, calls) = timers.take_due(&mut queue);
+            if !fired.is_empty() || !calls.is_empty() {
+                // The lock is released while sending so that timers can be added meanwhile.
+                drop(queue);
+                for call in calls {
+                    call();
+                }
+                for (id, aid, message) in fired {
+                    // A full channel must not hold up the timers of the other actors.
+                    match aid.try_send_without_blocking(message) {
+                        Ok(()) => (),
+                        Err(ActorError::ChannelFull) => {
+                            warn!("Channel of {:?} is full, dropping timer message", aid)
+                        }
+                        Err(e) => {
+                            warn!("Cancelling timer for {:?}: {:?}", aid, e);
+                            timers.cancel(id);
+                        }
+                    }
+                }
+                queue = timers.queue.lock().unwrap();
+                continue;
+            }
+
+            let wait = match queue.due.peek() {
+                Some(Reverse((deadline, _))) => {
+                    let until_due = deadline.saturating_duration_since(timers.clock.now());
+                    until_due.min(max_wait)
+                }
+                None => max_wait,
+            };
+            queue = timers.condvar.wait_timeout(queue, wait).unwrap().0;
+        }
+    })
+}
+
+impl ActorSystem {
+    /// Sends the `message` to the actor with the given `aid` once the `delay` has passed.
+    /// The returned handle can be used to cancel the timer before it fires.
+    ///
+    /// # Examples
+    /// ```
+    /// use axiom::*;
+    /// use std::time::Duration;
+    ///
+    /// let system = ActorSystem::create(ActorSystemConfig::default());
+    /// system.init_current();
+    ///
+    /// let aid = system.spawn(0, |_: &mut usize, _: ActorId, _: &Message| Status::Processed);
+    /// let timer = system.send_after(Message::new(11), aid, Duration::from_millis(500));
+    /// timer.cancel();
+    /// ```
+    pub fn send_after(&self, message: Message, aid: ActorId, delay: Duration) -> TimerHandle {
+        self.timers().add(aid, message, delay, None)
+    }
+
+    /// Sends the `message` to the actor with the given `aid` every `interval`, starting one
+    /// `interval` from now, until the returned handle is used to cancel the timer or the actor
+    /// stops.
+    pub fn send_interval(&self, message: Message, aid: ActorId, interval: Duration) -> TimerHandle {
+        self.timers().add(aid, message, interval, Some(interval))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+    use std::sync::mpsc;
+
+    /// Creates an actor system using a virtual clock.
+    fn create_virtual() -> (ActorSystem, Arc<VirtualClock>) {
+        let clock = Arc::new(VirtualClock::new());
+        let system = ActorSystem::create_with_clock(ActorSystemConfig::default(), clock.clone());
+        system.init_current();
+        (system, clock)
+    }
+
+    #[test]
+    fn test_send_after() {
+        init_test_log();
+        let (system, clock) = create_virtual();
+        let aid = system.spawn(0_usize, |_: &mut usize, _: ActorId, _: &Message| {
+            Status::Processed
+        });
+        let timer = system.send_after(Message::new(11), aid.clone(), Duration::from_secs(10));
+
+        // Nothing happens until the clock reaches the time the timer is due.
+        clock.advance(Duration::from_secs(9));
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(1, aid.received());
+        assert!(timer.is_active());
+
+        clock.advance(Duration::from_secs(1));
+        assert_await_received(&aid, 2, 1000);
+        assert!(!timer.is_active());
+
+        // A cancelled timer never fires.
+        let timer = system.send_after(Message::new(11), aid.clone(), Duration::from_secs(10));
+        timer.cancel();
+        assert!(!timer.is_active());
+        clock.advance(Duration::from_secs(20));
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(2, aid.received());
+
+        system.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_add_call() {
+        init_test_log();
+        let (system, clock) = create_virtual();
+        let calls = Arc::new(Mutex::new(0));
+        let add = |delay| {
+            let calls = calls.clone();
+            let call = Box::new(move || *calls.lock().unwrap() += 1);
+            system.timers().add_call(call, delay)
+        };
+
+        // The function is called once when the timer is due.
+        let timer = add(Duration::from_secs(10));
+        clock.advance(Duration::from_secs(9));
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(0, *calls.lock().unwrap());
+        assert!(timer.is_active());
+        clock.advance(Duration::from_secs(1));
+        assert_await(|| *calls.lock().unwrap() == 1, 1000);
+        assert!(!timer.is_active());
+
+        // A cancelled timer is never called.
+        let timer = add(Duration::from_secs(10));
+        timer.cancel();
+        assert!(!timer.is_active());
+        clock.advance(Duration::from_secs(20));
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(1, *calls.lock().unwrap());
+
+        system.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_full_channel() {
+        init_test_log();
+        let (system, clock) = create_virtual();
+        // The actor holds on to its first message until released. The message stays in the
+        // channel until it is processed, so the channel is full meanwhile.
+        let (started, starting) = mpsc::channel::<()>();
+        let (release, released) = mpsc::channel::<()>();
+        let full = system
+            .actor_builder()
+            .channel_size(1)
+            .spawn(
+                Mutex::new((started, released)),
+                |state: &mut Mutex<(mpsc::Sender<()>, mpsc::Receiver<()>)>,
+                 _: ActorId,
+                 message: &Message| {
+                    if message.content_as::<i32>().is_some() {
+                        let (started, released) = &*state.get_mut().unwrap();
+                        started.send(()).unwrap();
+                        released.recv().unwrap();
+                    }
+                    Status::Processed
+                },
+            )
+            .unwrap();
+        full.send(Message::new(1));
+        starting.recv_timeout(Duration::from_millis(1000)).unwrap();
+        let other = system.spawn(0_usize, |_: &mut usize, _: ActorId, _: &Message| {
+            Status::Processed
+        });
+
+        // The timer message for the full channel is dropped and the other timer still fires.
+        system.send_after(Message::new(3), full.clone(), Duration::from_secs(10));
+        system.send_after(Message::new(4), other.clone(), Duration::from_secs(10));
+        clock.advance(Duration::from_secs(10));
+        assert_await_received(&other, 2, 1000);
+
+        release.send(()).unwrap();
+        assert_await_received(&full, 2, 1000);
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(2, full.received());
+        system.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_send_interval() {
+        init_test_log();
+        let (system, clock) = create_virtual();
+        let aid = system.spawn(0_usize, |_: &mut usize, _: ActorId, _: &Message| {
+            Status::Processed
+        });
+        let timer = system.send_interval(Message::new(11), aid.clone(), Duration::from_secs(1));
+
+        for count in 2..5 {
+            clock.advance(Duration::from_secs(1));
+            assert_await_received(&aid, count, 1000);
+        }
+        timer.cancel();
+        clock.advance(Duration::from_secs(5));
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(4, aid.received());
+
+        system.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_cancel_on_stop() {
+        init_test_log();
+        let (system, clock) = create_virtual();
+        let aid = system.spawn(0_usize, |_: &mut usize, _: ActorId, _: &Message| {
+            Status::Processed
+        });
+        let interval = system.send_interval(Message::new(11), aid.clone(), Duration::from_secs(1));
+        let once = system.send_after(Message::new(11), aid.clone(), Duration::from_secs(1));
+
+        // Stopping the actor cancels all of its timers.
+        system.stop(aid);
+        assert!(!interval.is_active());
+        assert!(!once.is_active());
+        clock.advance(Duration::from_secs(1));
+
+        system.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_idle_timer() {
+        init_test_log();
+        let (system, clock) = create_virtual();
+        let aid = system.spawn(0_usize, |_: &mut usize, _: ActorId, _: &Message| {
+            Status::Processed
+        });
+        let timers = system.timers();
+        let (timer, idle) = timers.add_idle(aid.clone(), Message::new(11), Duration::from_secs(10));
+
+        // Activity postpones the timer without adding to the queue.
+        for _ in 0..100 {
+            clock.advance(Duration::from_millis(50));
+            idle.touch(timers.now());
+        }
+        assert_eq!(1, timers.queue.lock().unwrap().due.len());
+        clock.advance(Duration::from_secs(9));
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(1, aid.received());
+
+        // Once the actor has been idle for the duration the timer fires, and again after
+        // being idle for another duration.
+        clock.advance(Duration::from_secs(1));
+        assert_await_received(&aid, 2, 1000);
+        clock.advance(Duration::from_secs(10));
+        assert_await_received(&aid, 3, 1000);
+        assert!(timer.is_active());
+        assert_eq!(1, timers.queue.lock().unwrap().due.len());
+
+        timer.cancel();
+        clock.advance(Duration::from_secs(10));
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(3, aid.received());
+
+        system.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_real_clock() {
+        init_test_log();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+        let aid = system.spawn(0_usize, |_: &mut usize, _: ActorId, _: &Message| {
+            Status::Processed
+        });
+
+        let start = Instant::now();
+        system.send_after(Message::new(11), aid.clone(), Duration::from_millis(50));
+        assert_await_received(&aid, 2, 1000);
+        assert!(start.elapsed() >= Duration::from_millis(50));
+
+        system.trigger_and_await_shutdown();
+    }
+}
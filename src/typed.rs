@@ -0,0 +1,300 @@
+//! Implements typed actor ids that only accept messages of the type the actor handles.
+//!
+//! An actor spawned with [`ActorSystem::spawn_typed`] or [`ActorBuilder::spawn_typed`] handles
+//! messages of a single type `M` and its processor is handed an `&M` instead of a [`Message`]
+//! that has to be probed with [`Message::content_as`]. The spawn returns a [`TypedActorId<M>`]
+//! whose `send` only accepts an `M`, so sending the wrong type is caught by the compiler. The
+//! typed id wraps an [`ActorId`] which is still available for linking, monitoring, stopping and
+//! anything else that works with untyped ids.
+//!
+//! A message of another type that reaches a typed actor through its untyped id is logged and
+//! dropped. System messages are handled by the actor system as they would be for an actor that
+//! returns [`Status::Stop`] for a [`SystemMsg::Stop`] and [`Status::Processed`] for the others.
+
+use crate::actors::*;
+use crate::message::*;
+use log::warn;
+use serde::de::{DeserializeOwned, Deserializer};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+use std::any;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
+
+/// A type for a function that processes the messages of a typed actor.
+///
+/// This is the typed counterpart of [`Processor`]. The processor takes three arguments:
+/// * `state`   - A mutable reference to the current state of the actor.
+/// * `aid`     - The [`TypedActorId`] of this actor.
+/// * `message` - The content of the current message.
+pub trait TypedProcessor<State: Send + Sync, M: ActorMessage>:
+    (FnMut(&mut State, TypedActorId<M>, &M) -> Status) + Send + Sync
+{
+}
+
+// Allows any function, static or closure, to be used as a typed processor.
+impl<F, State, M> TypedProcessor<State, M> for F
+where
+    State: Send + Sync + 'static,
+    M: ActorMessage,
+    F: (FnMut(&mut State, TypedActorId<M>, &M) -> Status) + Send + Sync + 'static,
+{
+}
+
+/// An [`ActorId`] of an actor that handles messages of type `M`, which are the only messages
+/// that can be sent through it.
+///
+/// # Examples
+/// ```
+/// use axiom::*;
+///
+/// let system = ActorSystem::create(ActorSystemConfig::default());
+/// system.init_current();
+///
+/// let aid: TypedActorId<String> = system.spawn_typed(
+///     0 as usize,
+///     |count: &mut usize, _aid: TypedActorId<String>, message: &String| {
+///         *count += message.len();
+///         Status::Processed
+///     },
+/// );
+///
+/// aid.send("hello".to_string());
+/// ```
+pub struct TypedActorId<M> {
+    /// The untyped id of the actor.
+    aid: ActorId,
+    /// Ties the id to the message type without owning one.
+    phantom: PhantomData<fn(M)>,
+}
+
+impl<M: ActorMessage> TypedActorId<M> {
+    /// Wraps the untyped `aid` of an actor that handles messages of type `M`. Note that this
+    /// isn't checked; messages sent to an actor that doesn't handle `M` are ignored by it.
+    pub fn from_aid(aid: ActorId) -> TypedActorId<M> {
+        TypedActorId {
+            aid,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the untyped id of the actor.
+    pub fn aid(&self) -> &ActorId {
+        &self.aid
+    }
+
+    /// Sends the `message` to the actor as with [`ActorId::send`].
+    pub fn send(&self, message: M) {
+        self.aid.send(Message::new(message))
+    }
+
+    /// Attempts to send the `message` to the actor as with [`ActorId::try_send`].
+    pub fn try_send(&self, message: M) -> Result<(), ActorError> {
+        self.aid.try_send(Message::new(message))
+    }
+
+    /// Sends the `message` to the actor with the given `priority` as with
+    /// [`ActorId::send_with_priority`].
+    pub fn send_with_priority(&self, message: M, priority: u8) {
+        self.aid.send_with_priority(Message::new(message), priority)
+    }
+
+    /// Attempts to send the `message` to the actor with the given `priority` as with
+    /// [`ActorId::try_send_with_priority`].
+    pub fn try_send_with_priority(&self, message: M, priority: u8) -> Result<(), ActorError> {
+        self.aid
+            .try_send_with_priority(Message::new(message), priority)
+    }
+}
+
+impl<M> Clone for TypedActorId<M> {
+    fn clone(&self) -> Self {
+        TypedActorId {
+            aid: self.aid.clone(),
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<M> From<TypedActorId<M>> for ActorId {
+    fn from(typed: TypedActorId<M>) -> ActorId {
+        typed.aid
+    }
+}
+
+impl<M> PartialEq for TypedActorId<M> {
+    fn eq(&self, other: &TypedActorId<M>) -> bool {
+        self.aid == other.aid
+    }
+}
+
+impl<M> Eq for TypedActorId<M> {}
+
+impl<M> Hash for TypedActorId<M> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.aid.hash(state);
+    }
+}
+
+impl<M> fmt::Debug for TypedActorId<M> {
+    fn fmt(&self, formatter: &'_ mut fmt::Formatter) -> fmt::Result {
+        write!(
+            formatter,
+            "TypedActorId<{}>{{{:?}}}",
+            any::type_name::<M>(),
+            self.aid
+        )
+    }
+}
+
+// A typed id is serialized as its untyped id so the two can be read back as either.
+impl<M> Serialize for TypedActorId<M> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.aid.serialize(serializer)
+    }
+}
+
+impl<'de, M> Deserialize<'de> for TypedActorId<M> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(TypedActorId {
+            aid: ActorId::deserialize(deserializer)?,
+            phantom: PhantomData,
+        })
+    }
+}
+
+impl ActorBuilder {
+    /// Spawns a typed actor that handles messages of type `M` using the given starting `state`
+    /// and the `processor` function that will be used to process its messages. Otherwise this
+    /// is the same as [`ActorBuilder::spawn`].
+    pub fn spawn_typed<M, F, State>(
+        self,
+        state: State,
+        mut processor: F,
+    ) -> Result<TypedActorId<M>, ActorError>
+    where
+        State: Send + Sync + 'static,
+        M: ActorMessage + DeserializeOwned,
+        F: TypedProcessor<State, M> + 'static,
+    {
+        let aid = self.spawn(
+            state,
+            move |state: &mut State, aid: ActorId, message: &Message| {
+                if let Some(content) = message.content_as::<M>() {
+                    processor(state, TypedActorId::from_aid(aid), &content)
+                } else if let Some(system_msg) = message.content_as::<SystemMsg>() {
+                    match *system_msg {
+                        SystemMsg::Stop => Status::Stop,
+                        _ => Status::Processed,
+                    }
+                } else {
+                    warn!(
+                        "{:?} dropped a message that isn't a {}",
+                        aid,
+                        any::type_name::<M>()
+                    );
+                    Status::Processed
+                }
+            },
+        )?;
+        Ok(TypedActorId::from_aid(aid))
+    }
+}
+
+impl ActorSystem {
+    /// Spawns a new unnamed typed actor on the system that handles messages of type `M` using
+    /// the given starting `state` and the `processor` function. See [`TypedActorId`].
+    pub fn spawn_typed<M, F, State>(&self, state: State, processor: F) -> TypedActorId<M>
+    where
+        State: Send + Sync + 'static,
+        M: ActorMessage + DeserializeOwned,
+        F: TypedProcessor<State, M> + 'static,
+    {
+        self.actor_builder().spawn_typed(state, processor).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::*;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Command {
+        Add(i32),
+        Reset,
+    }
+
+    #[test]
+    fn test_typed_actor() {
+        init_test_log();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+
+        let totals: Arc<Mutex<Vec<i32>>> = Arc::new(Mutex::new(Vec::new()));
+        let aid = system.spawn_typed(
+            (0, totals.clone()),
+            |state: &mut (i32, Arc<Mutex<Vec<i32>>>),
+             _: TypedActorId<Command>,
+             command: &Command| {
+                match command {
+                    Command::Add(value) => state.0 += value,
+                    Command::Reset => state.0 = 0,
+                }
+                state.1.lock().unwrap().push(state.0);
+                Status::Processed
+            },
+        );
+        aid.send(Command::Add(2));
+        aid.send(Command::Add(3));
+        aid.send(Command::Reset);
+        assert_await_received(aid.aid(), 4, 1000);
+        assert_eq!(vec![2, 5, 0], *totals.lock().unwrap());
+
+        // A message of another type sent through the untyped id is dropped.
+        aid.aid().send(Message::new(11));
+        aid.send(Command::Add(1));
+        assert_await_received(aid.aid(), 6, 1000);
+        assert_eq!(vec![2, 5, 0, 1], *totals.lock().unwrap());
+        assert!(system.is_alive(aid.aid()));
+
+        // The untyped id is the same actor.
+        let untyped: ActorId = aid.clone().into();
+        assert_eq!(aid.aid(), &untyped);
+        assert_eq!(aid, TypedActorId::from_aid(untyped));
+
+        // A stop message stops the actor.
+        aid.aid().send(Message::new(SystemMsg::Stop));
+        assert_await(|| !system.is_alive(aid.aid()), 1000);
+
+        system.trigger_and_await_shutdown();
+    }
+
+    #[test]
+    fn test_typed_actor_id_serialization() {
+        init_test_log();
+        let system = ActorSystem::create(ActorSystemConfig::default());
+        system.init_current();
+        let aid = system.spawn_typed(0, |_: &mut usize, _: TypedActorId<i32>, _: &i32| {
+            Status::Processed
+        });
+
+        // Typed and untyped ids serialize the same way.
+        let serialized = serde_json::to_string(&aid).unwrap();
+        assert_eq!(serde_json::to_string(aid.aid()).unwrap(), serialized);
+        let deserialized: TypedActorId<i32> = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(aid, deserialized);
+        deserialized.send(11);
+        assert_await_received(aid.aid(), 2, 1000);
+
+        system.trigger_and_await_shutdown();
+    }
+}